@@ -0,0 +1,18 @@
+// Scripts for the pages generated by mdbook-luacats.
+
+// Open any collapsed sections containing the linked anchor, so following a
+// link to a symbol inside a <details> element shows it.
+(function () {
+    function reveal() {
+        var id = decodeURIComponent(window.location.hash.slice(1));
+        var target = id && document.getElementById(id);
+        for (var node = target; node; node = node.parentElement) {
+            if (node.tagName === "DETAILS") {
+                node.open = true;
+            }
+        }
+    }
+
+    window.addEventListener("hashchange", reveal);
+    reveal();
+})();
@@ -0,0 +1,145 @@
+//! Annotations authored inside descriptions, like `@order 1`.
+//!
+//! LuaLS passes unknown tags through as part of `rawdesc`, so they're parsed
+//! from there and removed before the description is rendered.
+
+/// Tags that are parsed from their own line of a description.
+const TAGS: &[&str] = &["@hidden", "@order", "@package", "@private", "@protected", "@see", "@tag"];
+
+/// The tag starting a usage example, which continues over the following
+/// lines until the next tag.
+const USAGE_TAG: &str = "@usage";
+
+/// The values of every line in the description starting with `tag`.
+fn tag_values<'a>(desc: &'a str, tag: &'a str) -> impl Iterator<Item = &'a str> {
+    desc.lines().filter_map(move |line| {
+        let rest = line.trim_start().strip_prefix(tag)?;
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        Some(rest.trim())
+    })
+}
+
+fn is_tag_line(line: &str) -> bool {
+    TAGS.iter().chain([&USAGE_TAG]).any(|tag| starts_with_tag(line, tag))
+}
+
+fn starts_with_tag(line: &str, tag: &str) -> bool {
+    line.trim_start()
+        .strip_prefix(tag)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Split the description's lines into `@usage` examples and everything
+/// else.
+fn split_usages(desc: &str) -> (Vec<Vec<&str>>, Vec<&str>) {
+    let mut usages: Vec<Vec<&str>> = Vec::new();
+    let mut rest = Vec::new();
+    let mut in_usage = false;
+
+    for line in desc.lines() {
+        if starts_with_tag(line, USAGE_TAG) {
+            in_usage = true;
+            let example = line.trim_start()[USAGE_TAG.len()..].trim();
+            usages.push(if example.is_empty() { Vec::new() } else { vec![example] });
+        } else if in_usage && !line.trim_start().starts_with('@') {
+            usages.last_mut().expect("Usage started").push(line);
+        } else {
+            in_usage = false;
+            rest.push(line);
+        }
+    }
+
+    (usages, rest)
+}
+
+/// The position requested by an `@order N` annotation.
+pub fn order(desc: &str) -> Option<i64> {
+    tag_values(desc, "@order").find_map(|value| value.parse().ok())
+}
+
+/// The labels added by `@tag X` annotations, in the order they're written.
+pub fn tags(desc: &str) -> Vec<&str> {
+    tag_values(desc, "@tag").filter(|value| !value.is_empty()).collect()
+}
+
+/// The targets of `@see` annotations, each a symbol name or URL optionally
+/// followed by text describing it.
+pub fn see(desc: &str) -> Vec<&str> {
+    tag_values(desc, "@see").filter(|value| !value.is_empty()).collect()
+}
+
+/// The code of each `@usage` example, without any fence around it.
+pub fn usages(desc: &str) -> Vec<String> {
+    split_usages(desc)
+        .0
+        .into_iter()
+        .map(|lines| {
+            let mut code = lines.join("\n").trim().to_owned();
+            if let Some(fenced) = code.strip_prefix("```").and_then(|code| code.strip_suffix("```")) {
+                // Drop the fence's info string along with the fence.
+                code = fenced.split_once('\n').map_or("", |(_, code)| code).trim().to_owned();
+            }
+            code
+        })
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+/// Whether the description has the annotation, like `@hidden`.
+pub fn has_tag(desc: &str, tag: &str) -> bool {
+    tag_values(desc, tag).next().is_some()
+}
+
+/// The description with annotation lines removed.
+pub fn strip(desc: &str) -> String {
+    split_usages(desc)
+        .1
+        .into_iter()
+        .filter(|line| !is_tag_line(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_order() {
+        let desc = "Do the thing.\n@order 2";
+
+        assert_eq!(order(desc), Some(2));
+        assert_eq!(order("@ordered 2"), None);
+        assert_eq!(strip(desc), "Do the thing.");
+    }
+
+    #[test]
+    fn parse_hidden() {
+        assert!(has_tag("Internal.\n@hidden", "@hidden"));
+        assert!(has_tag("@package", "@package"));
+        assert!(!has_tag("Not @private at all", "@private"));
+        assert!(!has_tag("@privately", "@private"));
+    }
+
+    #[test]
+    fn parse_usages() {
+        let desc = "Do the thing.\n@usage local x = thing()\nprint(x)\n@order 1\n@usage\n```lua\nthing()\n```\n@example not usage";
+
+        assert_eq!(usages(desc), vec!["local x = thing()\nprint(x)", "thing()"]);
+        assert_eq!(strip(desc), "Do the thing.\n@example not usage");
+        assert!(usages("@usaged thing()").is_empty());
+    }
+
+    #[test]
+    fn parse_tags() {
+        let desc = "Do the thing.\n@tag experimental\n@tag\n@tag   unstable";
+
+        assert_eq!(tags(desc), vec!["experimental", "unstable"]);
+        assert_eq!(strip(desc), "Do the thing.");
+    }
+}
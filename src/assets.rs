@@ -0,0 +1,78 @@
+//! Bundled CSS and JS for styling the generated docs in the HTML renderer.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use mdbook::renderer::RenderContext;
+use rust_embed::Embed;
+
+#[derive(Embed)]
+#[folder = "assets"]
+struct Assets;
+
+/// The folder below the HTML output the assets are written to.
+pub const ASSETS_DIR: &str = "luacats";
+
+/// The HTML renderer's output folder, for the `mdbook-luacats assets`
+/// renderer configured alongside it.
+///
+/// With more than one renderer each one writes below a folder of its own, so
+/// the HTML is always in `<build-dir>/html`.
+pub fn html_output_dir(ctx: &RenderContext) -> PathBuf {
+    ctx.root.join(&ctx.config.build.build_dir).join("html")
+}
+
+/// Write the bundled assets to the `luacats` folder below the book's HTML
+/// output, returning the paths written.
+///
+/// mdbook's HTML renderer clears its output before rendering, so this runs as
+/// a renderer of its own after it rather than from the preprocessor.
+pub fn write<P: AsRef<Path>>(output_dir: P) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = output_dir.as_ref().join(ASSETS_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let mut written = Vec::new();
+    for name in Assets::iter() {
+        let asset = Assets::get(&name).expect("embedded asset");
+        let path = dir.join(name.as_ref());
+        fs::write(&path, asset.data)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// The HTML referencing the assets from the chapter at `page`.
+pub fn references(page: &Path) -> String {
+    let depth = page.components().count().saturating_sub(1);
+    let prefix = format!("{}{}", "../".repeat(depth), ASSETS_DIR);
+
+    format!(
+        "<link rel=\"stylesheet\" href=\"{0}/luacats.css\">\n<script src=\"{0}/luacats.js\" defer></script>",
+        prefix
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use mdbook::{book::Book, Config};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn write_assets() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new("luacats-assets")?;
+        let ctx = RenderContext::new(tmp_dir.path(), Book::new(), Config::default(), tmp_dir.path().join("book/luacats-assets"));
+
+        let written = write(html_output_dir(&ctx))?;
+
+        assert_eq!(written.len(), 2);
+        assert!(tmp_dir.path().join("book/html/luacats/luacats.css").is_file());
+        assert!(tmp_dir.path().join("book/html/luacats/luacats.js").is_file());
+        assert!(references(Path::new("renoise/midi.lua")).contains("href=\"../luacats/luacats.css\""));
+
+        Ok(())
+    }
+}
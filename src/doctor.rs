@@ -0,0 +1,152 @@
+//! Setup diagnostics for the `doctor` subcommand.
+use std::{
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The result of checking whether docs can be generated.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Report {
+    /// The command used to invoke the language server.
+    pub luals_command: String,
+    /// The reported language server version, or why it couldn't be run.
+    pub luals_version: Result<String, String>,
+    /// The definitions path that was checked.
+    pub definitions_path: PathBuf,
+    /// Whether the definitions path exists and is a directory.
+    pub path_exists: bool,
+    /// The number of `.lua` files found below the definitions path.
+    pub lua_file_count: usize,
+}
+
+impl Report {
+    /// Check the given language server command and definitions path.
+    pub fn check<P: Into<PathBuf>>(luals_command: &str, definitions_path: P) -> Self {
+        let definitions_path = definitions_path.into();
+        let path_exists = definitions_path.is_dir();
+        let lua_file_count = if path_exists {
+            count_lua_files(&definitions_path).unwrap_or(0)
+        } else {
+            0
+        };
+
+        Self {
+            luals_command: luals_command.to_owned(),
+            luals_version: luals_version(luals_command),
+            definitions_path,
+            path_exists,
+            lua_file_count,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.luals_version.is_ok() && self.lua_file_count > 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.luals_version {
+            Ok(version) => writeln!(f, "{}: found ({})", self.luals_command, version)?,
+            Err(problem) => writeln!(f, "{}: {}", self.luals_command, problem)?,
+        }
+
+        let path = self.definitions_path.display();
+        if !self.path_exists {
+            writeln!(f, "definitions path: {} (missing)", path)?;
+        } else {
+            writeln!(f, "definitions path: {} ({} .lua files)", path, self.lua_file_count)?;
+        }
+
+        if self.is_ready() {
+            write!(f, "Ready to generate docs.")
+        } else {
+            write!(f, "Not ready: fix the problems above and try again.")
+        }
+    }
+}
+
+fn luals_version(luals_command: &str) -> Result<String, String> {
+    let output = Command::new(luals_command)
+        .arg("--version")
+        .output()
+        .map_err(|_| "not found".to_owned())?;
+    if !output.status.success() {
+        return Err(format!("failed to run ({})", output.status));
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if version.is_empty() {
+        return Ok("unknown version".into());
+    }
+
+    Ok(version)
+}
+
+fn count_lua_files(dir: &Path) -> io::Result<usize> {
+    let mut count = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            count += count_lua_files(&path)?;
+        } else if path.extension().is_some_and(|ext| ext == "lua") {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn doctor_report() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new("luacats-doctor")?;
+
+        let stub = tmp_dir.path().join("lua-language-server");
+        fs::write(&stub, "#!/bin/sh\necho 3.9.3\n")?;
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755))?;
+
+        let library = tmp_dir.path().join("library");
+        fs::create_dir_all(library.join("renoise"))?;
+        fs::write(library.join("renoise.lua"), "---@meta\n")?;
+        fs::write(library.join("renoise/midi.lua"), "---@meta\n")?;
+
+        let report = Report::check(stub.to_str().unwrap(), &library);
+        let output = report.to_string();
+
+        assert!(report.is_ready());
+        assert!(output.contains("found (3.9.3)"), "{}", output);
+        assert!(output.contains(&format!("{} (2 .lua files)", library.display())), "{}", output);
+
+        let missing = Report::check(stub.to_str().unwrap(), tmp_dir.path().join("missing"));
+
+        assert!(!missing.is_ready());
+        assert!(missing.to_string().contains("(missing)"));
+
+        let broken = tmp_dir.path().join("broken-language-server");
+        fs::write(&broken, "#!/bin/sh
+echo 3.9.3
+exit 2
+")?;
+        fs::set_permissions(&broken, fs::Permissions::from_mode(0o755))?;
+
+        let failed = Report::check(broken.to_str().unwrap(), &library);
+        let output = failed.to_string();
+
+        assert!(!failed.is_ready());
+        assert!(output.contains("failed to run (exit status: 2)"), "{}", output);
+
+        Ok(())
+    }
+}
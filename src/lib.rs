@@ -1,4 +1,10 @@
+pub mod annotations;
+pub mod assets;
 pub mod workspace;
 pub mod lua_cats;
+pub mod links;
 pub mod luals;
-pub mod preprocess;
\ No newline at end of file
+pub mod manifest;
+pub mod markdown;
+pub mod preprocess;
+pub mod doctor;
\ No newline at end of file
@@ -0,0 +1,587 @@
+//! Cross-linking between documented symbols.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{markdown::anchor, workspace::MetaFile};
+
+/// Type names that are never linked, even if a definition shares the name.
+///
+/// These are Lua's builtin types and LuaLS pseudo-types like `self`, which
+/// would otherwise resolve to unrelated definitions (the `string` library,
+/// say).
+pub const PSEUDO_TYPES: &[&str] = &[
+    "any",
+    "boolean",
+    "false",
+    "function",
+    "integer",
+    "lightuserdata",
+    "nil",
+    "number",
+    "self",
+    "string",
+    "table",
+    "thread",
+    "true",
+    "unknown",
+    "userdata",
+    "void",
+];
+
+/// Whether a type name may be linked to its definition.
+pub fn is_linkable(name: &str) -> bool {
+    !PSEUDO_TYPES.contains(&name)
+}
+
+/// The chapter and anchor each documented symbol can be linked at.
+///
+/// Targets are stored relative to the book's source folder, e.g.
+/// `renoise/song.md#renoise-song`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SymbolIndex {
+    targets: BTreeMap<String, String>,
+    /// The base classes of each class, for resolving inherited members.
+    #[serde(skip)]
+    bases: BTreeMap<String, Vec<String>>,
+    /// The heading anchor of each symbol by name, file and start offset,
+    /// kept unique within its chapter.
+    #[serde(skip)]
+    anchors: BTreeMap<(String, String, u64), String>,
+    /// The anchors already used in each chapter.
+    #[serde(skip)]
+    chapter_anchors: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl SymbolIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index the definitions and class members of the files and their sub files.
+    pub fn from_files(files: &[MetaFile]) -> Self {
+        Self::with_nav_depth(files, None)
+    }
+
+    /// Index the files and their sub files, with files nested deeper than
+    /// `nav_depth` chapters linked in their nearest ancestor's chapter.
+    pub fn with_nav_depth(files: &[MetaFile], nav_depth: Option<usize>) -> Self {
+        let mut index = Self::new();
+
+        for file in files {
+            index.insert_nested(file, nav_depth);
+        }
+
+        index
+    }
+
+    /// Index the definitions and class members of a file and its sub files.
+    pub fn insert_file(&mut self, file: &MetaFile) {
+        self.insert_nested(file, None);
+    }
+
+    /// Index a top level file and its sub files, with files nested deeper
+    /// than `nav_depth` chapters linked in their nearest ancestor's chapter.
+    pub fn insert_nested(&mut self, file: &MetaFile, nav_depth: Option<usize>) {
+        self.insert_in_chapter(file, None, 1, nav_depth);
+    }
+
+    fn insert_in_chapter(&mut self, file: &MetaFile, folded_into: Option<&Path>, depth: usize, nav_depth: Option<usize>) {
+        let chapter = match folded_into {
+            Some(chapter) => chapter.to_owned(),
+            None => file.path.with_extension("md"),
+        };
+
+        for definition in file.definitions.iter() {
+            match definition.defines.first() {
+                Some(define) => self.insert_at(&definition.name, &define.file, define.start, &chapter),
+                None => self.insert(&definition.name, &chapter),
+            }
+
+            let bases = definition.bases();
+            if !bases.is_empty() {
+                self.bases
+                    .entry(definition.name.clone())
+                    .or_default()
+                    .extend(bases.into_iter().map(str::to_owned));
+            }
+
+            for field in definition.fields.iter() {
+                self.insert_at(&field.qualified_name(&definition.name), &field.file, field.start, &chapter);
+            }
+        }
+
+        let fold = folded_into.is_some() || nav_depth.is_some_and(|nav_depth| depth >= nav_depth);
+        for sub_file in file.sub_files.iter() {
+            self.insert_in_chapter(sub_file, fold.then_some(chapter.as_path()), depth + 1, nav_depth);
+        }
+    }
+
+    /// Add a symbol documented in the given chapter. The first chapter a
+    /// symbol is added for wins.
+    pub fn insert(&mut self, name: &str, chapter: &Path) {
+        let chapter = chapter.to_string_lossy().replace('\\', "/");
+
+        self.targets
+            .entry(name.to_owned())
+            .or_insert_with(|| format!("{}#{}", chapter, anchor(name)));
+    }
+
+    /// Add a symbol defined at `start` in `file`, giving it an anchor that's
+    /// unique within the chapter.
+    ///
+    /// Like mdbook, the first symbol keeps its anchor and later ones with the
+    /// same anchor get a `-1`, `-2`... suffix.
+    pub fn insert_at(&mut self, name: &str, file: &str, start: u64, chapter: &Path) {
+        let key = (name.to_owned(), file.to_owned(), start);
+        let chapter = chapter.to_string_lossy().replace('\\', "/");
+
+        let unique = match self.anchors.get(&key) {
+            Some(unique) => unique.clone(),
+            None => {
+                let used = self.chapter_anchors.entry(chapter.clone()).or_default();
+                let base = anchor(name);
+                let mut unique = base.clone();
+                let mut count = 0;
+                while used.contains(&unique) {
+                    count += 1;
+                    unique = format!("{}-{}", base, count);
+                }
+                used.insert(unique.clone());
+                self.anchors.insert(key, unique.clone());
+                unique
+            }
+        };
+
+        self.targets
+            .entry(name.to_owned())
+            .or_insert_with(|| format!("{}#{}", chapter, unique));
+    }
+
+    /// The heading anchor of the symbol defined at `start` in `file`, or the
+    /// anchor computed from its name when it isn't indexed.
+    pub fn anchor(&self, name: &str, file: &str, start: u64) -> String {
+        self.anchors
+            .get(&(name.to_owned(), file.to_owned(), start))
+            .cloned()
+            .unwrap_or_else(|| anchor(name))
+    }
+
+    /// The link target for a symbol, relative to the book's source folder.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        if !is_linkable(name) {
+            return None;
+        }
+
+        self.targets.get(name).map(String::as_str)
+    }
+
+    /// The qualified name of the nearest base class member a class's member
+    /// overrides, searching the inheritance chain breadth first.
+    pub fn overridden(&self, class: &str, member: &str) -> Option<String> {
+        let mut visited = vec![class];
+        let mut pending: Vec<&str> = self.bases_of(class).collect();
+
+        while !pending.is_empty() {
+            for base in pending.iter() {
+                for separator in [':', '.'] {
+                    let name = format!("{}{}{}", base, separator, member);
+                    if self.targets.contains_key(&name) {
+                        return Some(name);
+                    }
+                }
+            }
+
+            visited.extend(pending.iter());
+            pending = pending
+                .iter()
+                .flat_map(|base| self.bases_of(base))
+                .filter(|base| !visited.contains(base))
+                .unique()
+                .collect();
+        }
+
+        None
+    }
+
+    /// The classes that directly extend a class, sorted by name.
+    pub fn subclasses(&self, class: &str) -> Vec<&str> {
+        self.bases
+            .iter()
+            .filter(|(_, bases)| bases.iter().any(|base| base == class))
+            .map(|(subclass, _)| subclass.as_str())
+            .collect()
+    }
+
+    /// A nested list of the classes extending a class, directly or through
+    /// each other, linked from the chapter at `page`.
+    pub fn subclass_tree(&self, class: &str, page: &Path) -> String {
+        let mut tree = String::new();
+        let mut visited = vec![class];
+        let mut pending: Vec<(&str, usize)> = self.subclasses(class).into_iter().rev().map(|subclass| (subclass, 0)).collect();
+
+        while let Some((subclass, depth)) = pending.pop() {
+            if visited.contains(&subclass) {
+                continue;
+            }
+            visited.push(subclass);
+
+            let name = match self.link(subclass, page) {
+                Some(target) => format!("[`{}`]({})", subclass, target),
+                None => format!("`{}`", subclass),
+            };
+            tree.push_str(&format!("{}- {}\n", "  ".repeat(depth), name));
+            pending.extend(self.subclasses(subclass).into_iter().rev().map(|subclass| (subclass, depth + 1)));
+        }
+
+        tree
+    }
+
+    fn bases_of<'a>(&'a self, class: &str) -> impl Iterator<Item = &'a str> {
+        self.bases.get(class).into_iter().flatten().map(String::as_str)
+    }
+
+    /// The link target for a symbol from the chapter at `page`.
+    pub fn link(&self, name: &str, page: &Path) -> Option<String> {
+        self.get(name).map(|target| relative_link(page, target))
+    }
+
+    /// Render an `@see` target as a link from the chapter at `page`.
+    ///
+    /// URLs are linked as they are, using any text after them as the link
+    /// text, while symbols are linked to their definitions when documented.
+    pub fn see_link(&self, see: &str, page: &Path) -> String {
+        let (target, text) = match see.split_once(char::is_whitespace) {
+            Some((target, text)) => (target, Some(text.trim())),
+            None => (see, None),
+        };
+
+        let link = if is_url(target) {
+            format!("[{}]({})", text.unwrap_or(target), target)
+        } else {
+            match self.link(target, page) {
+                Some(link) => format!("[`{}`]({})", target, link),
+                None => format!("`{}`", target),
+            }
+        };
+
+        match text {
+            Some(text) if !is_url(target) => format!("{} {}", link, text),
+            _ => link,
+        }
+    }
+
+    /// Link the inline code spans in Markdown that name documented symbols,
+    /// like `` `renoise.Song` ``. Other spans, spans already inside links and
+    /// code blocks are left as they are.
+    pub fn link_code_spans(&self, markdown: &str, page: &Path) -> String {
+        let mut in_fence = false;
+        let mut lines = Vec::new();
+
+        for line in markdown.split('\n') {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if in_fence || !line.contains('`') {
+                lines.push(line.to_owned());
+                continue;
+            }
+
+            let mut output = String::new();
+            let mut rest = line;
+            while let Some(start) = rest.find('`') {
+                let Some(len) = rest[start + 1..].find('`') else {
+                    break;
+                };
+                let end = start + 1 + len;
+                let symbol = &rest[start + 1..end];
+                let linked = rest[..start].ends_with('[');
+
+                output.push_str(&rest[..start]);
+                match self.link(symbol, page) {
+                    Some(target) if !linked => output.push_str(&format!("[`{}`]({})", symbol, target)),
+                    _ => output.push_str(&rest[start..=end]),
+                }
+                rest = &rest[end + 1..];
+            }
+            output.push_str(rest);
+            lines.push(output);
+        }
+
+        lines.join("\n")
+    }
+
+    /// Render a signature as HTML code, linking any documented types.
+    ///
+    /// Names followed by `:` or `(` are parameters and functions being
+    /// declared rather than types, so they're never linked.
+    pub fn link_signature(&self, view: &str, page: &Path) -> String {
+        let tokens = tokenize(view);
+        let mut output = String::new();
+
+        for (index, token) in tokens.iter().enumerate() {
+            let declared = tokens
+                .get(index + 1)
+                .is_some_and(|next| next.starts_with(':') || next.starts_with('('));
+
+            match self.link(token, page) {
+                Some(target) if is_identifier(token) && !declared => {
+                    output.push_str(&format!("<a href=\"{}\">{}</a>", target, token));
+                }
+                _ => output.push_str(&escape_html(token)),
+            }
+        }
+
+        output
+    }
+
+    /// Render a type view as inline code, linking any documented types.
+    ///
+    /// Markdown can't contain links inside a code span so linked types are
+    /// split into their own spans, e.g. ``[`Song`](song.md#song)`|nil` ``.
+    pub fn link_types(&self, view: &str, page: &Path) -> String {
+        let mut output = String::new();
+        let mut code = String::new();
+        let mut linked = false;
+
+        for token in tokenize(view) {
+            match self.link(token, page) {
+                Some(target) if is_identifier(token) => {
+                    if !code.is_empty() {
+                        output.push_str(&format!("`{}`", code));
+                        code.clear();
+                    }
+                    output.push_str(&format!("[`{}`]({})", token, target));
+                    linked = true;
+                }
+                _ => code.push_str(token),
+            }
+        }
+
+        if !linked {
+            return format!("`{}`", view);
+        }
+
+        if !code.is_empty() {
+            output.push_str(&format!("`{}`", code));
+        }
+
+        output
+    }
+}
+
+/// Escape text for use in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Split a union type like `"a"|"b"|nil` into its members, ignoring `|`s
+/// nested inside brackets or strings.
+pub fn union_members(view: &str) -> Vec<&str> {
+    let mut members = Vec::new();
+    let mut depth = 0i32;
+    let mut quote = None;
+    let mut start = 0;
+
+    for (index, c) in view.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '<' | '{' | '[') => depth += 1,
+            (None, ')' | '>' | '}' | ']') => depth -= 1,
+            (None, '|') if depth == 0 => {
+                members.push(view[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    members.push(view[start..].trim());
+
+    members
+}
+
+/// Split a type view into dotted identifiers and the text between them.
+fn tokenize(view: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_identifier = false;
+
+    for (index, c) in view.char_indices() {
+        let identifier_char = c.is_alphanumeric() || c == '_' || (in_identifier && c == '.');
+        if identifier_char != in_identifier && index > start {
+            tokens.push(&view[start..index]);
+            start = index;
+        }
+        in_identifier = identifier_char;
+    }
+
+    if start < view.len() {
+        tokens.push(&view[start..]);
+    }
+
+    tokens
+}
+
+fn is_url(target: &str) -> bool {
+    target.starts_with("https://") || target.starts_with("http://")
+}
+
+fn is_identifier(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+/// A link to `target` (relative to the book's source folder) from the
+/// chapter at `page`.
+pub fn relative_link(page: &Path, target: &str) -> String {
+    let (target_path, fragment) = target.split_once('#').unwrap_or((target, ""));
+    let page = page.with_extension("md");
+
+    if Path::new(target_path) == page {
+        return format!("#{}", fragment);
+    }
+
+    let page_dir: Vec<Component> = page.parent().into_iter().flat_map(Path::components).collect();
+    let target_components: Vec<Component> = Path::new(target_path).components().collect();
+    let common = page_dir
+        .iter()
+        .zip(target_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut link = PathBuf::new();
+    for _ in common..page_dir.len() {
+        link.push("..");
+    }
+    for component in &target_components[common..] {
+        link.push(component);
+    }
+
+    let link = link.to_string_lossy().replace('\\', "/");
+    if fragment.is_empty() {
+        link
+    } else {
+        format!("{}#{}", link, fragment)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn link_types() {
+        let mut index = SymbolIndex::new();
+        index.insert("renoise.Song", Path::new("renoise/song.md"));
+        index.insert("self", Path::new("self.md"));
+
+        let page = Path::new("renoise/midi.lua");
+
+        assert_eq!(index.link_types("renoise.Song", page), "[`renoise.Song`](song.md#renoise-song)");
+        assert_eq!(
+            index.link_types("renoise.Song|nil", Path::new("bit.lua")),
+            "[`renoise.Song`](renoise/song.md#renoise-song)`|nil`"
+        );
+        assert_eq!(index.link_types("self", page), "`self`");
+        assert_eq!(index.link_types("self", Path::new("renoise/song.lua")), "`self`");
+    }
+
+    #[test]
+    fn link_signature() {
+        let mut index = SymbolIndex::new();
+        index.insert("renoise.Song", Path::new("renoise/song.md"));
+        index.insert("renoise.Instrument", Path::new("renoise/instrument.md"));
+        index.insert("song", Path::new("song.md"));
+
+        assert_eq!(
+            index.link_signature("function song(song: renoise.Song, x: Unknown<T>)\n  -> renoise.Instrument", Path::new("renoise.lua")),
+            concat!(
+                "function song(song: <a href=\"renoise/song.md#renoise-song\">renoise.Song</a>, x: Unknown&lt;T&gt;)\n",
+                "  -&gt; <a href=\"renoise/instrument.md#renoise-instrument\">renoise.Instrument</a>"
+            )
+        );
+    }
+
+    #[test]
+    fn split_unions() {
+        assert_eq!(union_members("\"a\"|\"b|c\"|fun(x: string|nil)|nil"), vec!["\"a\"", "\"b|c\"", "fun(x: string|nil)", "nil"]);
+        assert_eq!(union_members("string"), vec!["string"]);
+    }
+
+    #[test]
+    fn see_links() {
+        let mut index = SymbolIndex::new();
+        index.insert("renoise.Song", Path::new("renoise/song.md"));
+        let page = Path::new("renoise.lua");
+
+        assert_eq!(index.see_link("https://example.com", page), "[https://example.com](https://example.com)");
+        assert_eq!(index.see_link("https://example.com The docs", page), "[The docs](https://example.com)");
+        assert_eq!(index.see_link("renoise.Song", page), "[`renoise.Song`](renoise/song.md#renoise-song)");
+        assert_eq!(index.see_link("other for more", page), "`other` for more");
+    }
+
+    #[test]
+    fn link_code_spans() {
+        let mut index = SymbolIndex::new();
+        index.insert("renoise.Song", Path::new("renoise/song.md"));
+        let page = Path::new("renoise.lua");
+
+        assert_eq!(
+            index.link_code_spans("Returns a `renoise.Song`, or `nil`.\n```lua\nlocal s = `renoise.Song`\n```\n[`renoise.Song`](x)", page),
+            "Returns a [`renoise.Song`](renoise/song.md#renoise-song), or `nil`.\n```lua\nlocal s = `renoise.Song`\n```\n[`renoise.Song`](x)"
+        );
+        assert_eq!(index.link_code_spans("An unclosed ` tick", page), "An unclosed ` tick");
+    }
+
+    #[test]
+    fn subclass_tree() {
+        let mut index = SymbolIndex::new();
+        index.insert("Dog", Path::new("dog.md"));
+        index.bases.insert("Dog".into(), vec!["Animal".into()]);
+        index.bases.insert("Cat".into(), vec!["Animal".into()]);
+        index.bases.insert("Puppy".into(), vec!["Dog".into()]);
+        index.bases.insert("Animal".into(), vec!["Puppy".into()]);
+
+        assert_eq!(index.subclasses("Animal"), vec!["Cat", "Dog"]);
+        assert_eq!(
+            index.subclass_tree("Animal", Path::new("animal.lua")),
+            "- `Cat`\n- [`Dog`](dog.md#dog)\n  - `Puppy`\n"
+        );
+    }
+
+    #[test]
+    fn overridden_members() {
+        let mut index = SymbolIndex::new();
+        index.insert("Animal:speak", Path::new("animal.md"));
+        index.bases.insert("Dog".into(), vec!["Pet".into()]);
+        index.bases.insert("Pet".into(), vec!["Animal".into(), "Dog".into()]);
+
+        assert_eq!(index.overridden("Dog", "speak").as_deref(), Some("Animal:speak"));
+        assert_eq!(index.overridden("Dog", "fetch"), None);
+        assert_eq!(index.overridden("Animal", "speak"), None);
+    }
+
+    #[test]
+    fn unique_anchors() {
+        let mut index = SymbolIndex::new();
+        index.insert_at("Song.play", "file:///song.lua", 10, Path::new("song.md"));
+        index.insert_at("Song:play", "file:///song.lua", 20, Path::new("song.md"));
+        index.insert_at("Song:play", "file:///song.lua", 20, Path::new("song.md"));
+        index.insert_at("Song:play", "file:///other.lua", 0, Path::new("other.md"));
+
+        assert_eq!(index.anchor("Song.play", "file:///song.lua", 10), "song-play");
+        assert_eq!(index.anchor("Song:play", "file:///song.lua", 20), "song-play-1");
+        assert_eq!(index.anchor("Song:play", "file:///other.lua", 0), "song-play");
+        assert_eq!(index.anchor("Song:stop", "file:///song.lua", 30), "song-stop");
+        assert_eq!(index.get("Song:play"), Some("song.md#song-play-1"));
+    }
+}
@@ -1,18 +1,300 @@
 //!LuaCATS documentation types.
-use std::{fmt, marker::PhantomData};
+use std::{collections::BTreeMap, fmt, marker::PhantomData};
 
 use serde::{de::{self, MapAccess, Visitor}, Deserialize, Deserializer, Serialize};
 
+use crate::{annotations, links};
+
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Definition {
     pub desc: Option<String>,
     pub rawdesc: Option<String>,
+    /// Descriptions keyed by locale, for LuaLS versions that export them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rawdescs: BTreeMap<String, String>,
     pub name: String,
     #[serde(rename = "type")]
     pub lua_type: DefinitionType,
     pub defines: Vec<Define>,
     #[serde(default)]
     pub fields: Vec<Field>,
+    /// Whether the definition is annotated with `---@deprecated`.
+    #[serde(default)]
+    pub deprecated: bool,
+    /// The visibility LuaLS reports, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible: Option<Visibility>,
+}
+
+impl Definition {
+    /// The description in the given locale, or the default description.
+    pub fn description(&self, locale: Option<&str>) -> Option<&str> {
+        description(self.rawdesc.as_deref(), &self.rawdescs, locale)
+    }
+
+    /// The position requested by an `@order N` annotation, if any.
+    pub fn order(&self) -> Option<i64> {
+        self.rawdesc.as_deref().and_then(annotations::order)
+    }
+
+    /// Who the definition is meant for.
+    pub fn visibility(&self) -> Visibility {
+        visibility(self.visible, self.rawdesc.as_deref())
+    }
+
+    /// Whether the definition is private, package or annotated as hidden.
+    pub fn is_hidden(&self) -> bool {
+        is_hidden(self.visibility(), self.rawdesc.as_deref())
+    }
+
+    /// Whether the definition is a `---@class`.
+    pub fn is_class(&self) -> bool {
+        self.defines
+            .iter()
+            .any(|define| define.lua_type == DefinitionType::DocClass)
+    }
+
+    /// Whether the definition is a table, like a module's `renoise.song = {}`.
+    pub fn is_table(&self) -> bool {
+        self.kind() == Kind::Variable
+            && self
+                .defines
+                .iter()
+                .flat_map(|define| define.extends.first())
+                .any(|extend| extend.lua_type == DefinitionType::Table)
+    }
+
+    /// What sort of symbol the definition is.
+    pub fn kind(&self) -> Kind {
+        let define_types: Vec<DefinitionType> = self.defines.iter().map(|define| define.lua_type).collect();
+
+        if define_types.contains(&DefinitionType::DocClass) {
+            Kind::Class
+        } else if define_types.contains(&DefinitionType::DocEnum) {
+            Kind::Enum
+        } else if define_types.contains(&DefinitionType::DocAlias) {
+            Kind::Alias
+        } else if self
+            .defines
+            .iter()
+            .flat_map(|define| define.extends.first())
+            .any(|extend| extend.lua_type == DefinitionType::Function)
+        {
+            Kind::Function
+        } else {
+            Kind::Variable
+        }
+    }
+
+    /// The names of the classes this class extends, e.g. `Base` from
+    /// `---@class Derived: Base`.
+    pub fn bases(&self) -> Vec<&str> {
+        self.defines
+            .iter()
+            .filter(|define| define.lua_type == DefinitionType::DocClass)
+            .flat_map(|define| define.extends.iter())
+            .filter(|extend| extend.lua_type == DefinitionType::DocExtendsName)
+            .map(|extend| extend.view.as_str())
+            .collect()
+    }
+
+    /// The function signatures across every define, without repeats. There's
+    /// more than one when the function has `---@overload`s.
+    pub fn signatures(&self) -> Vec<&Extend> {
+        signatures(self.defines.iter().flat_map(|define| define.extends.iter()))
+    }
+
+    /// The namespace the definition is declared in, e.g. `renoise.song` for
+    /// `renoise.song.Track`, or an empty string for globals.
+    pub fn namespace(&self) -> &str {
+        self.name
+            .rfind(['.', ':'])
+            .map_or("", |index| &self.name[..index])
+    }
+
+    /// The signatures a class can be called with, from its `__call`
+    /// metamethod, for classes that are also functions.
+    pub fn call_signatures(&self) -> Vec<&Extend> {
+        if self.kind() != Kind::Class {
+            return Vec::new();
+        }
+
+        signatures(
+            self.fields
+                .iter()
+                .filter(|field| field.name == "__call")
+                .flat_map(|field| field.extends.iter())
+                .filter(|extend| extend.lua_type == DefinitionType::Function),
+        )
+    }
+
+    /// The members of a `---@enum`, in source order, with their values
+    /// taken from the enum table where LuaLS shows them.
+    pub fn enum_members(&self) -> Vec<EnumMember> {
+        if self.kind() != Kind::Enum {
+            return Vec::new();
+        }
+
+        let entries: Vec<TableEntry> = self
+            .defines
+            .iter()
+            .flat_map(|define| define.extends.iter())
+            .flat_map(Extend::table_entries)
+            .collect();
+
+        let mut fields: Vec<&Field> = self.fields.iter().filter(|field| !field.is_method()).collect();
+        fields.sort_by(|a, b| (&a.file, a.start).cmp(&(&b.file, b.start)));
+
+        // A member can be listed more than once, e.g. for the table field
+        // and a `---@field` documenting it, so the descriptions are merged.
+        let mut members: Vec<EnumMember> = Vec::new();
+        for field in fields {
+            let rawdesc = field.rawdesc.clone().or_else(|| field.desc.clone());
+            if let Some(member) = members.iter_mut().find(|member| member.name == field.name) {
+                member.rawdesc = member.rawdesc.take().or(rawdesc);
+                for (locale, desc) in field.rawdescs.iter() {
+                    member.rawdescs.entry(locale.clone()).or_insert_with(|| desc.clone());
+                }
+                continue;
+            }
+
+            let value = entries
+                .iter()
+                .find(|entry| entry.key == field.name)
+                .and_then(|entry| entry.value.clone())
+                .or_else(|| field.view().filter(|view| is_literal(view)).map(str::to_owned));
+
+            members.push(EnumMember {
+                name: field.name.clone(),
+                qualified_name: field.qualified_name(&self.name),
+                file: field.file.clone(),
+                start: field.start,
+                value,
+                rawdesc,
+                rawdescs: field.rawdescs.clone(),
+            });
+        }
+
+        members
+    }
+}
+
+/// Bucket definitions by their [namespace](Definition::namespace), keeping
+/// their order within each namespace.
+pub fn group_by_namespace(defs: &[Definition]) -> BTreeMap<String, Vec<Definition>> {
+    let mut groups: BTreeMap<String, Vec<Definition>> = BTreeMap::new();
+    for definition in defs {
+        groups
+            .entry(definition.namespace().to_owned())
+            .or_default()
+            .push(definition.clone());
+    }
+
+    groups
+}
+
+/// A member of a `---@enum`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct EnumMember {
+    pub name: String,
+    pub qualified_name: String,
+    /// The file and start offset of the field defining the member.
+    pub file: String,
+    pub start: u64,
+    /// The member's constant value, if known.
+    pub value: Option<String>,
+    pub rawdesc: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rawdescs: BTreeMap<String, String>,
+}
+
+/// Whether a view is a literal value, like `1` or `"red"`, rather than a type.
+fn is_literal(view: &str) -> bool {
+    view.parse::<f64>().is_ok()
+        || matches!(view, "true" | "false")
+        || (view.len() > 1 && (view.starts_with('"') || view.starts_with('\'')) && view.ends_with(&view[..1]))
+}
+
+/// Who a definition or field is meant for, from `---@private`,
+/// `---@protected` and `---@package`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    /// Only for the class and its subclasses.
+    Protected,
+    /// Only for the class.
+    Private,
+    /// Only for the file.
+    Package,
+}
+
+/// The visibility LuaLS reports, falling back to the annotations left in the
+/// description.
+fn visibility(visible: Option<Visibility>, rawdesc: Option<&str>) -> Visibility {
+    if let Some(visible) = visible {
+        return visible;
+    }
+
+    let rawdesc = rawdesc.unwrap_or_default();
+    [
+        ("@private", Visibility::Private),
+        ("@package", Visibility::Package),
+        ("@protected", Visibility::Protected),
+    ]
+    .into_iter()
+    .find(|(tag, _)| annotations::has_tag(rawdesc, tag))
+    .map_or(Visibility::Public, |(_, visibility)| visibility)
+}
+
+fn is_hidden(visibility: Visibility, rawdesc: Option<&str>) -> bool {
+    matches!(visibility, Visibility::Private | Visibility::Package)
+        || rawdesc.is_some_and(|rawdesc| annotations::has_tag(rawdesc, "@hidden"))
+}
+
+/// The sort of symbol a definition or field documents, as readers think of
+/// it rather than how LuaLS records it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Alias,
+    Class,
+    Enum,
+    Field,
+    Function,
+    Method,
+    Variable,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 7] = [
+        Kind::Alias,
+        Kind::Class,
+        Kind::Enum,
+        Kind::Field,
+        Kind::Function,
+        Kind::Method,
+        Kind::Variable,
+    ];
+
+    /// The kind's lowercase name, as used in config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Alias => "alias",
+            Kind::Class => "class",
+            Kind::Enum => "enum",
+            Kind::Field => "field",
+            Kind::Function => "function",
+            Kind::Method => "method",
+            Kind::Variable => "variable",
+        }
+    }
+
+    /// The kind by its lowercase name.
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -28,6 +310,10 @@ pub enum DefinitionType {
     DocExtendsName,
     #[serde(rename = "doc.enum")]
     DocEnum,
+    #[serde(rename = "doc.field")]
+    DocField,
+    #[serde(rename = "doc.operator")]
+    DocOperator,
     #[serde(rename = "doc.type")]
     DocType,
     Function,
@@ -49,6 +335,9 @@ pub enum DefinitionType {
     Variable,
     #[serde(rename = "...")]
     VarArg,
+    /// Any type added by a newer LuaLS release than this crate knows.
+    #[serde(other, rename = "unknown")]
+    Unknown,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -63,11 +352,75 @@ pub struct Define {
     pub extends: Vec<Extend>,
 }
 
+impl Define {
+    /// The 1-based line the definition starts on.
+    pub fn line(&self) -> u64 {
+        line(self.start)
+    }
+
+    /// The size of the definition's source span, `finish - start`.
+    ///
+    /// Positions are encoded LuaLS positions, so this is only a hint for
+    /// comparing definitions, not a byte count.
+    pub fn size(&self) -> u64 {
+        span_size(self.start, self.finish)
+    }
+}
+
+/// The distance between two LuaLS positions.
+pub fn span_size(start: u64, finish: u64) -> u64 {
+    finish.saturating_sub(start)
+}
+
+/// Convert a LuaLS position to a 1-based line number.
+///
+/// LuaLS encodes positions as `line * 10000 + column`, with 0-based lines.
+pub fn line(position: u64) -> u64 {
+    position / 10_000 + 1
+}
+
+/// The metamethods Lua looks up in a metatable.
+pub const METAMETHODS: &[&str] = &[
+    "__add",
+    "__band",
+    "__bnot",
+    "__bor",
+    "__bxor",
+    "__call",
+    "__close",
+    "__concat",
+    "__div",
+    "__eq",
+    "__gc",
+    "__idiv",
+    "__index",
+    "__ipairs",
+    "__le",
+    "__len",
+    "__lt",
+    "__metatable",
+    "__mod",
+    "__mode",
+    "__mul",
+    "__name",
+    "__newindex",
+    "__pairs",
+    "__pow",
+    "__shl",
+    "__shr",
+    "__sub",
+    "__tostring",
+    "__unm",
+];
+
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Field {
     pub name: String,
     pub desc: Option<String>,
     pub rawdesc: Option<String>,
+    /// Descriptions keyed by locale, for LuaLS versions that export them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rawdescs: BTreeMap<String, String>,
     pub start: u64,
     pub finish: u64,
     #[serde(rename = "type")]
@@ -76,6 +429,95 @@ pub struct Field {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_extends")]
     pub extends: Vec<Extend>,
+    /// Whether the field is annotated with `---@deprecated`.
+    #[serde(default)]
+    pub deprecated: bool,
+    /// The visibility LuaLS reports, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible: Option<Visibility>,
+}
+
+impl Field {
+    /// The description in the given locale, or the default description.
+    pub fn description(&self, locale: Option<&str>) -> Option<&str> {
+        description(self.rawdesc.as_deref(), &self.rawdescs, locale)
+    }
+
+    /// The field's name qualified by its parent's, e.g. `Class:method`.
+    pub fn qualified_name(&self, parent: &str) -> String {
+        qualified_name(parent, &self.name, self.lua_type)
+    }
+
+    /// Whether the field is a method or function rather than a value.
+    pub fn is_method(&self) -> bool {
+        self.lua_type == DefinitionType::SetMethod
+            || self
+                .extends
+                .first()
+                .is_some_and(|extend| extend.lua_type == DefinitionType::Function)
+    }
+
+    /// The function signatures of the field, without repeats.
+    pub fn signatures(&self) -> Vec<&Extend> {
+        signatures(self.extends.iter())
+    }
+
+    /// Whether the field is an `---@operator` or a metamethod like `__add`.
+    pub fn is_operator(&self) -> bool {
+        self.lua_type == DefinitionType::DocOperator || METAMETHODS.contains(&self.name.as_str())
+    }
+
+    /// Who the field is meant for.
+    pub fn visibility(&self) -> Visibility {
+        visibility(self.visible, self.rawdesc.as_deref())
+    }
+
+    /// Whether the field is private, package or annotated as hidden.
+    pub fn is_hidden(&self) -> bool {
+        is_hidden(self.visibility(), self.rawdesc.as_deref())
+    }
+
+    /// What sort of member the field is.
+    pub fn kind(&self) -> Kind {
+        if self.is_method() {
+            Kind::Method
+        } else {
+            Kind::Field
+        }
+    }
+
+    /// The field's type, as shown by LuaLS.
+    pub fn view(&self) -> Option<&str> {
+        self.extends.first().map(|extend| extend.view.as_str())
+    }
+}
+
+/// The function extends with distinct views, in the order they're defined.
+fn signatures<'a>(extends: impl Iterator<Item = &'a Extend>) -> Vec<&'a Extend> {
+    let mut signatures: Vec<&Extend> = Vec::new();
+    for extend in extends.filter(|extend| extend.lua_type == DefinitionType::Function) {
+        if !signatures.iter().any(|signature| signature.view == extend.view) {
+            signatures.push(extend);
+        }
+    }
+
+    signatures
+}
+
+/// Select the description for a locale, falling back to the default `rawdesc`
+/// when there isn't one in that locale.
+pub fn description<'a>(rawdesc: Option<&'a str>, rawdescs: &'a BTreeMap<String, String>, locale: Option<&str>) -> Option<&'a str> {
+    locale
+        .and_then(|locale| rawdescs.get(locale))
+        .map(String::as_str)
+        .or(rawdesc)
+}
+
+/// Qualify a member name by its parent's, joining methods with `:`.
+pub fn qualified_name(parent: &str, name: &str, lua_type: DefinitionType) -> String {
+    let separator = if lua_type == DefinitionType::SetMethod { ':' } else { '.' };
+
+    format!("{}{}{}", parent, separator, name)
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -94,6 +536,154 @@ pub struct Extend {
     /// Only present for functions (type = "function") with returns
     #[serde(default)]
     pub returns: Vec<FuncReturn>,
+    /// The members of union types, like those of an `---@alias`
+    #[serde(default)]
+    pub types: Vec<UnionMember>,
+}
+
+impl Extend {
+    /// The members of a union type, with any descriptions written after
+    /// them like `---| "red" # The color red`.
+    ///
+    /// Falls back to splitting the view when LuaLS doesn't list the members.
+    pub fn union_members(&self) -> Vec<UnionMember> {
+        if !self.types.is_empty() {
+            return self.types.clone();
+        }
+
+        links::union_members(&self.view)
+            .into_iter()
+            .map(|view| UnionMember {
+                view: view.to_owned(),
+                desc: None,
+                rawdesc: None,
+            })
+            .collect()
+    }
+
+    /// The generic type parameters in the view, in the order they appear.
+    ///
+    /// LuaLS shows generics as `<T>`, or `<T:Base>` when they're constrained
+    /// with `---@generic T: Base`.
+    pub fn type_params(&self) -> Vec<TypeParam> {
+        let mut params: Vec<TypeParam> = Vec::new();
+        let mut rest = self.view.as_str();
+
+        while let Some(open) = rest.find('<') {
+            // `table<K, V>` and friends are type applications, not generics
+            let applied = rest[..open]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
+            let Some(close) = rest[open..].find('>').map(|close| open + close) else {
+                break;
+            };
+
+            if !applied {
+                let (name, constraint) = match rest[open + 1..close].split_once(':') {
+                    Some((name, constraint)) => (name.trim(), Some(constraint.trim().to_owned())),
+                    None => (rest[open + 1..close].trim(), None),
+                };
+                let is_name = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+                if is_name && !params.iter().any(|param| param.name == name) {
+                    params.push(TypeParam {
+                        name: name.to_owned(),
+                        constraint,
+                    });
+                }
+            }
+
+            rest = &rest[close + 1..];
+        }
+
+        params
+    }
+}
+
+impl Extend {
+    /// The keys of a table literal's view, like `{ debug: boolean = false }`.
+    ///
+    /// Returns nothing if the extend isn't a table or its view can't be read.
+    pub fn table_entries(&self) -> Vec<TableEntry> {
+        if self.lua_type != DefinitionType::Table {
+            return Vec::new();
+        }
+        let view = self.view.trim();
+        let Some(body) = view.strip_prefix('{').and_then(|view| view.strip_suffix('}')) else {
+            return Vec::new();
+        };
+
+        let mut entries = Vec::new();
+        let mut depth = 0i32;
+        let mut quote = None;
+        let mut start = 0;
+        for (index, c) in body.char_indices().chain([(body.len(), ',')]) {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '(' | '<' | '{' | '[') => depth += 1,
+                (None, ')' | '>' | '}' | ']') => depth -= 1,
+                (None, ',' | '\n') if depth == 0 => {
+                    let entry = body[start..index].trim();
+                    if !entry.is_empty() {
+                        match TableEntry::parse(entry) {
+                            Some(entry) => entries.push(entry),
+                            None => return Vec::new(),
+                        }
+                    }
+                    start = index + c.len_utf8();
+                }
+                _ => {}
+            }
+        }
+
+        entries
+    }
+}
+
+/// One of the types in a union.
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct UnionMember {
+    pub view: String,
+    pub desc: Option<String>,
+    pub rawdesc: Option<String>,
+}
+
+/// A key of a table literal, as shown in its view.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TableEntry {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub lua_type: String,
+    /// The literal value, if LuaLS shows it.
+    pub value: Option<String>,
+}
+
+impl TableEntry {
+    /// Parse an entry like `debug: boolean = false`.
+    fn parse(entry: &str) -> Option<Self> {
+        let (key, rest) = entry.split_once(':')?;
+        let (lua_type, value) = match rest.split_once(" = ") {
+            Some((lua_type, value)) => (lua_type, Some(value.trim().to_owned())),
+            None => (rest, None),
+        };
+
+        Some(Self {
+            key: key.trim().to_owned(),
+            lua_type: lua_type.trim().to_owned(),
+            value,
+        })
+    }
+}
+
+/// A generic type parameter, such as `T: Base`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TypeParam {
+    pub name: String,
+    /// The type the parameter must extend, if any.
+    pub constraint: Option<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -113,12 +703,49 @@ pub struct FuncArg {
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub struct FuncReturn {
+    /// The name is missing for unnamed returns
     pub name: Option<String>,
     #[serde(rename = "type")]
     pub lua_type: DefinitionType,
     pub view: String,
     pub desc: Option<String>,
     pub rawdesc: Option<String>,
+    /// Only present in newer LuaLS versions
+    #[serde(default)]
+    pub start: u64,
+    #[serde(default)]
+    pub finish: u64,
+}
+
+impl FuncArg {
+    /// Whether this is the variadic `...` argument.
+    pub fn is_vararg(&self) -> bool {
+        is_vararg(self.name.as_deref(), self.lua_type, &self.view)
+    }
+}
+
+impl FuncReturn {
+    /// Whether this is a variadic `...` return.
+    pub fn is_vararg(&self) -> bool {
+        is_vararg(self.name.as_deref(), self.lua_type, &self.view)
+    }
+}
+
+/// Whether an argument or return is variadic.
+///
+/// LuaLS marks variadic arguments with the `...` type and leaves them
+/// unnamed, while variadic returns are named `...` or have a `T...` view.
+pub fn is_vararg(name: Option<&str>, lua_type: DefinitionType, view: &str) -> bool {
+    lua_type == DefinitionType::VarArg || name == Some("...") || (name.is_none() && view.ends_with("..."))
+}
+
+/// The type of each value of a variadic argument or return, e.g. `string`
+/// for `string...`.
+pub fn vararg_type(view: &str) -> &str {
+    match view.trim_end_matches("...").trim() {
+        "" => "any",
+        view => view,
+    }
 }
 
 /// Implement the value of "extends", which may be missing, null, an array
@@ -144,6 +771,7 @@ where
             Ok(Vec::new())
         }
 
+        #[allow(clippy::needless_question_mark)]
         fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
             where
                 A: de::SeqAccess<'de>, { 
@@ -159,4 +787,49 @@ where
     }
 
     deserializer.deserialize_any(ExtendData(PhantomData))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn namespace_groups() -> anyhow::Result<()> {
+        let defs: Vec<Definition> = serde_json::from_str(r#"[
+            {"name": "renoise.song.Track", "type": "type", "defines": []},
+            {"name": "print", "type": "variable", "defines": []},
+            {"name": "renoise.song", "type": "variable", "defines": []},
+            {"name": "renoise.song.Pattern", "type": "type", "defines": []},
+            {"name": "renoise.Song:track", "type": "variable", "defines": []}
+        ]"#)?;
+
+        let groups = group_by_namespace(&defs);
+        let names: BTreeMap<&str, Vec<&str>> = groups
+            .iter()
+            .map(|(namespace, defs)| (namespace.as_str(), defs.iter().map(|def| def.name.as_str()).collect()))
+            .collect();
+
+        assert_eq!(names, BTreeMap::from([
+            ("", vec!["print"]),
+            ("renoise", vec!["renoise.song"]),
+            ("renoise.Song", vec!["renoise.Song:track"]),
+            ("renoise.song", vec!["renoise.song.Track", "renoise.song.Pattern"]),
+        ]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn operator_fields() -> anyhow::Result<()> {
+        let fields: Vec<Field> = serde_json::from_str(r#"[
+            {"name": "__add", "type": "setmethod", "start": 0, "finish": 0, "file": "file:///test.lua"},
+            {"name": "__index", "type": "setfield", "start": 0, "finish": 0, "file": "file:///test.lua"},
+            {"name": "__private", "type": "setfield", "start": 0, "finish": 0, "file": "file:///test.lua"}
+        ]"#)?;
+        let operators: Vec<bool> = fields.iter().map(Field::is_operator).collect();
+
+        assert_eq!(operators, vec![true, true, false]);
+
+        Ok(())
+    }
+}
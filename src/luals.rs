@@ -1,35 +1,458 @@
-use std::{fs::{self}, path::PathBuf, process::Command};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    fs::{self},
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
 use anyhow::{anyhow, Error};
+use itertools::Itertools;
+use log::{debug, warn};
+use serde_json::Value;
 use tempdir::TempDir;
-use crate::lua_cats::Definition;
+use crate::lua_cats::{Definition, DefinitionType};
+
+/// The command used to spawn the language server.
+pub const LUALS_COMMAND: &str = "lua-language-server";
+
+/// How long LuaLS may take to generate docs before it's killed.
+pub const DEFAULT_LUALS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The oldest LuaLS release whose doc.json format is supported.
+pub const SUPPORTED_LUALS_VERSION: &str = "3.7.0";
+
+/// The doc.json format a LuaLS release generated, relative to the format
+/// this crate reads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DocFormat {
+    /// Generated by a release before [`SUPPORTED_LUALS_VERSION`].
+    Older,
+    Supported,
+    /// Generated by a release that changed the format since.
+    Newer,
+}
+
+impl DocFormat {
+    /// Guess the format of a doc.json from its shape.
+    ///
+    /// This is best effort: older releases didn't include the `rawdesc` of
+    /// definitions, and the format has always been a list of definitions.
+    pub fn detect(doc: &Value) -> Self {
+        let Some(definitions) = doc.as_array() else {
+            return DocFormat::Newer;
+        };
+
+        let has_key = |key: &str| definitions.iter().any(|definition| definition.get(key).is_some());
+        if has_key("desc") && !has_key("rawdesc") {
+            return DocFormat::Older;
+        }
+
+        DocFormat::Supported
+    }
+
+    /// A warning about the format, if it isn't supported.
+    pub fn warning(self) -> Option<String> {
+        match self {
+            DocFormat::Older => Some(format!(
+                "The doc.json was generated by an older LuaLS than this preprocessor supports, so some docs may be missing. \
+                 Upgrade to lua-language-server {} or newer.",
+                SUPPORTED_LUALS_VERSION
+            )),
+            DocFormat::Supported => None,
+            DocFormat::Newer => Some(format!(
+                "The doc.json was generated by a newer LuaLS than this preprocessor supports and may not load. \
+                 Check for a newer mdbook-luacats, or use lua-language-server {}.",
+                SUPPORTED_LUALS_VERSION
+            )),
+        }
+    }
+}
+
+/// Parse the definitions in a doc.json, warning if its format isn't supported.
+pub fn parse_docs(json_doc: &str) -> Result<Vec<Definition>, Error> {
+    let doc: Value = serde_json::from_str(json_doc)?;
+    if let Some(warning) = DocFormat::detect(&doc).warning() {
+        warn!("{}", warning);
+    }
+    let unknown = unknown_types(&doc);
+    if !unknown.is_empty() {
+        warn!(
+            "The doc.json has types this version of mdbook-luacats doesn't know, which may not be documented correctly: {}",
+            unknown.into_iter().join(", ")
+        );
+    }
+
+    Ok(serde_json::from_value(doc)?)
+}
+
+/// The `type`s of the definitions, defines, fields, extends, arguments and
+/// returns in a doc.json which aren't a known [`DefinitionType`].
+pub fn unknown_types(doc: &Value) -> BTreeSet<String> {
+    fn visit(value: &Value, nested: &[&str], unknown: &mut BTreeSet<String>) {
+        let items: Vec<&Value> = match value {
+            Value::Array(items) => items.iter().collect(),
+            item => vec![item],
+        };
+        for item in items {
+            let Some(lua_type) = item.get("type").and_then(Value::as_str) else {
+                continue;
+            };
+            if serde_json::from_value::<DefinitionType>(Value::String(lua_type.to_owned())).is_ok_and(|lua_type| lua_type == DefinitionType::Unknown) {
+                unknown.insert(lua_type.to_owned());
+            }
+            for key in nested {
+                if let Some(children) = item.get(key) {
+                    visit(children, nested, unknown);
+                }
+            }
+        }
+    }
+
+    let mut unknown = BTreeSet::new();
+    visit(doc, &["defines", "fields", "extends", "args", "returns"], &mut unknown);
+
+    unknown
+}
+
+/// Read a doc.json generated ahead of time, e.g. by an earlier CI step.
+pub fn read_docs(doc_json_path: &Path) -> Result<Vec<Definition>, Error> {
+    let json_doc = fs::read_to_string(doc_json_path)
+        .map_err(|err| anyhow!("Unable to read {}: {}", doc_json_path.display(), err))?;
+
+    parse_docs(&json_doc)
+}
 
 /// Spawn the lua-language-server to generate docs.
 pub fn generate_docs(definitions_path: &PathBuf) -> Result<Vec<Definition>,Error> { 
+    generate_docs_with_timeout(definitions_path, DEFAULT_LUALS_TIMEOUT)
+}
+
+/// Spawn the lua-language-server to generate docs, killing it if it runs
+/// for longer than the timeout.
+pub fn generate_docs_with_timeout(definitions_path: &PathBuf, timeout: Duration) -> Result<Vec<Definition>, Error> {
+    parse_docs(&run_luals_doc(definitions_path, timeout)?)
+}
+
+/// Generate docs like [`generate_docs_with_timeout`], reusing the doc.json
+/// in `cache_dir` from the last run when the definitions haven't changed
+/// since.
+pub fn generate_docs_cached(definitions_path: &PathBuf, timeout: Duration, cache_dir: &Path) -> Result<Vec<Definition>, Error> {
+    let key = match docs_cache_key(definitions_path) {
+        Ok(key) => key,
+        Err(err) => {
+            warn!("Unable to check for changes to {}, so docs aren't cached: {}", definitions_path.display(), err);
+            return generate_docs_with_timeout(definitions_path, timeout);
+        }
+    };
+    let mut hasher = DefaultHasher::new();
+    definitions_path.hash(&mut hasher);
+    let name = format!("{:016x}", hasher.finish());
+    let key_path = cache_dir.join(format!("{}.key", name));
+    let json_doc_path = cache_dir.join(format!("{}.json", name));
+
+    if fs::read_to_string(&key_path).is_ok_and(|cached_key| cached_key == key) {
+        if let Ok(json_doc) = fs::read_to_string(&json_doc_path) {
+            debug!("Using cached docs for {:?} from {:?}", definitions_path, json_doc_path);
+            return parse_docs(&json_doc);
+        }
+    }
+
+    let json_doc = run_luals_doc(definitions_path, timeout)?;
+
+    // The key is removed first and written last so an interrupted write
+    // can't pair a key with the wrong docs.
+    let cached = fs::create_dir_all(cache_dir)
+        .and_then(|_| match fs::remove_file(&key_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        })
+        .and_then(|_| fs::write(&json_doc_path, &json_doc))
+        .and_then(|_| fs::write(&key_path, &key));
+    if let Err(err) = cached {
+        warn!("Unable to cache docs in {}: {}", cache_dir.display(), err);
+    }
+
+    parse_docs(&json_doc)
+}
+
+/// A hash of the LuaLS version, and the path, size and modification time of
+/// every `.lua` and `.luarc.json` file below the definitions path, along with
+/// any `.luarc.json` in the folders above it such as the book root, which
+/// changes whenever LuaLS is upgraded or a file is added, edited or deleted.
+pub fn docs_cache_key(definitions_path: &Path) -> Result<String, Error> {
+    let mut files = Vec::new();
+    find_doc_inputs(definitions_path, &mut files)?;
+    let absolute_path = fs::canonicalize(definitions_path)?;
+    for dir in absolute_path.ancestors().skip(1) {
+        for name in [".luarc.json", ".luarc.jsonc"] {
+            let path = dir.join(name);
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    let luals_version = Command::new(LUALS_COMMAND)
+        .arg("--version")
+        .output()
+        .map(|output| output.stdout)
+        .unwrap_or_default();
+    luals_version.hash(&mut hasher);
+    for file in files {
+        let metadata = fs::metadata(&file)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        (file, metadata.len(), modified).hash(&mut hasher);
+    }
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn find_doc_inputs(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_doc_inputs(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "lua")
+            || path.file_name().is_some_and(|name| name == ".luarc.json" || name == ".luarc.jsonc")
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `lua-language-server --doc`, returning the doc.json it generates.
+fn run_luals_doc(definitions_path: &PathBuf, timeout: Duration) -> Result<String, Error> {
     let tmp_dir = TempDir::new("luals-docs")?;
     let tmp_path = tmp_dir.path();
 
-    let output = Command::new("lua-language-server")
+    let mut command = Command::new(LUALS_COMMAND);
+    command
         .arg("--doc")
         .arg(definitions_path)
         .arg("--doc_out_path")
         .arg(tmp_path)
         .arg("--logpath")
-        .arg(tmp_path)
-        .output()?;
+        .arg(tmp_path);
+
+    let output = output_with_timeout(&mut command, timeout)?;
 
     if !output.status.success() {
         let err = match output.status.code() {
             Some(code) => anyhow!("LuaLS process exited with status code {}", code),
             None => anyhow!("LuaLS process terminated by signal"),
         };
-        return Err(err)
+        return Err(anyhow!("{}\n\nCommand: {:?}\n\nStderr:\n{}", err, command, stderr_excerpt(&output.stderr)))
     }
 
     let json_doc_path = tmp_dir.path().join("doc.json");
 
-    let json_doc = fs::read_to_string(json_doc_path)?;
+    Ok(fs::read_to_string(json_doc_path)?)
+}
+
+/// Run the command to completion, like [`Command::output`], unless the
+/// timeout passes first, in which case the process is killed.
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output, Error> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("Unable to run {:?}: {}", command, err))?;
+
+    // Drain the pipes while waiting so a chatty process can't block on them
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(anyhow!(
+                "LuaLS process was killed after running for {}\n\nCommand: {:?}",
+                humantime::format_duration(timeout),
+                command
+            ));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// The most stderr kept in errors. LuaLS prints the cause last, so it's
+/// the start that gets cut.
+const MAX_STDERR_LEN: usize = 4000;
+
+/// The end of the decoded stderr, truncated if it's huge.
+fn stderr_excerpt(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim_end();
+    if stderr.len() <= MAX_STDERR_LEN {
+        return stderr.to_owned();
+    }
+
+    let mut start = stderr.len() - MAX_STDERR_LEN;
+    while !stderr.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...{}", &stderr[start..])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_doc_format() -> anyhow::Result<()> {
+        let older = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "desc": "Say hello.",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///hello.lua"}]
+        }]"#;
+        let format = DocFormat::detect(&serde_json::from_str(older)?);
+        assert_eq!(format, DocFormat::Older);
+        assert!(format.warning().unwrap().contains("lua-language-server 3.7.0 or newer"));
+        assert_eq!(parse_docs(older)?.len(), 1);
+
+        let supported = older.replace(r#""desc": "Say hello.","#, r#""desc": "Say hello.", "rawdesc": "Say hello.","#);
+        assert_eq!(DocFormat::detect(&serde_json::from_str(&supported)?), DocFormat::Supported);
+        assert_eq!(DocFormat::Supported.warning(), None);
+
+        assert_eq!(DocFormat::detect(&serde_json::json!({"definitions": []})), DocFormat::Newer);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_after_timeout() -> anyhow::Result<()> {
+        let started = Instant::now();
+        let err = output_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(100)).unwrap_err();
+
+        assert!(err.to_string().starts_with("LuaLS process was killed after running for 100ms"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
 
-    let definitions: Vec<Definition> = serde_json::from_str(&json_doc)?;
+        let output = output_with_timeout(Command::new("sh").args(["-c", "echo out; echo err >&2"]), Duration::from_secs(10))?;
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
 
-    Ok(definitions)
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_definition_types() -> anyhow::Result<()> {
+        let json_doc = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///hello.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.future", "view": "future"}
+            }],
+            "fields": [{"name": "x", "type": "doc.field.future", "start": 0, "finish": 10, "file": "file:///hello.lua"}]
+        }]"#;
+
+        let docs = parse_docs(json_doc)?;
+        assert_eq!(docs[0].defines[0].extends[0].lua_type, DefinitionType::Unknown);
+        assert_eq!(
+            unknown_types(&serde_json::from_str(json_doc)?).into_iter().collect::<Vec<_>>(),
+            vec!["doc.field.future", "doc.future"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_stderr() {
+        assert_eq!(stderr_excerpt(b"bad .luarc.json\n"), "bad .luarc.json");
+
+        let huge = format!("{}cause", "é".repeat(MAX_STDERR_LEN));
+        let excerpt = stderr_excerpt(huge.as_bytes());
+        assert!(excerpt.starts_with("...é"));
+        assert!(excerpt.ends_with("cause"));
+        assert!(excerpt.len() <= MAX_STDERR_LEN + 3);
+    }
+
+    #[test]
+    fn cached_docs() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new("doc-cache")?;
+        let definitions_path = tmp_dir.path().join("library");
+        let cache_dir = tmp_dir.path().join("cache");
+        fs::create_dir_all(definitions_path.join("renoise"))?;
+        fs::write(definitions_path.join("renoise.lua"), "---@meta\n")?;
+        fs::write(definitions_path.join("renoise/midi.lua"), "---@meta\n")?;
+        fs::write(definitions_path.join("notes.txt"), "")?;
+
+        let key = docs_cache_key(&definitions_path)?;
+        assert_eq!(docs_cache_key(&definitions_path)?, key);
+        fs::write(definitions_path.join("notes.txt"), "ignored")?;
+        assert_eq!(docs_cache_key(&definitions_path)?, key);
+
+        // Prime the cache as if LuaLS already ran, so it's read instead
+        fs::create_dir_all(&cache_dir)?;
+        let mut hasher = DefaultHasher::new();
+        definitions_path.hash(&mut hasher);
+        let name = format!("{:016x}", hasher.finish());
+        fs::write(cache_dir.join(format!("{}.json", name)), r#"[{"name": "cached", "type": "variable", "rawdesc": "", "defines": []}]"#)?;
+        fs::write(cache_dir.join(format!("{}.key", name)), &key)?;
+        let docs = generate_docs_cached(&definitions_path, DEFAULT_LUALS_TIMEOUT, &cache_dir)?;
+        assert_eq!(docs[0].name, "cached");
+
+        fs::remove_file(definitions_path.join("renoise/midi.lua"))?;
+        assert_ne!(docs_cache_key(&definitions_path)?, key);
+
+        // A config in the book root applies to the definitions too
+        let key = docs_cache_key(&definitions_path)?;
+        fs::write(tmp_dir.path().join(".luarc.json"), "{}")?;
+        assert_ne!(docs_cache_key(&definitions_path)?, key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_generated_docs() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new("doc-json")?;
+        let path = tmp_dir.path().join("doc.json");
+        fs::write(&path, r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///hello.lua"}]
+        }]"#)?;
+
+        assert_eq!(read_docs(&path)?[0].name, "hello");
+        assert!(read_docs(&tmp_dir.path().join("missing.json")).unwrap_err().to_string().starts_with("Unable to read"));
+
+        Ok(())
+    }
 }
\ No newline at end of file
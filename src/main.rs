@@ -1,19 +1,100 @@
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use mdbook_luacats::assets;
+use mdbook_luacats::doctor::Report;
+use mdbook_luacats::luals::{generate_docs, LUALS_COMMAND};
+use mdbook_luacats::markdown::MarkdownOptions;
 use mdbook_luacats::preprocess::LuaCats;
+use mdbook_luacats::workspace::Workspace;
 use mdbook::errors::Error;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+use mdbook::renderer::RenderContext;
 use semver::{Version, VersionReq};
-use std::io;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::process;
 
 pub fn make_app() -> Command {
     Command::new("mdbook-luacats")
         .about("A mdbook preprocessor for generating luaCATS API docs")
+        .arg(
+            Arg::new("export-workspace")
+                .long("export-workspace")
+                .value_name("FILE")
+                .help("Also write the loaded workspace, with its definitions, to a JSON file"),
+        )
         .subcommand(
             Command::new("supports")
                 .arg(Arg::new("renderer").required(true))
                 .about("Check whether a renderer is supported by this preprocessor"),
         )
+        .subcommand(
+            Command::new("assets")
+                .about("Write the bundled CSS and JS into the HTML output, when run as a renderer after the HTML renderer")
+                .after_help("Configure it in book.toml below [output.html] with:\n\n    [output.luacats-assets]\n    command = \"mdbook-luacats assets\""),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .arg(
+                    Arg::new("definitions-path")
+                        .long("definitions-path")
+                        .default_value("library")
+                        .help("The folder containing LuaCATS definitions"),
+                )
+                .about("Check that lua-language-server and the definitions are set up"),
+        )
+        .subcommand(
+            Command::new("markdown")
+                .arg(
+                    Arg::new("definitions-path")
+                        .long("definitions-path")
+                        .default_value("library")
+                        .help("The folder containing LuaCATS definitions"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_name("PATH")
+                        .help("The definition file to render, e.g. renoise/midi.lua"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["markdown", "json"])
+                        .default_value("markdown")
+                        .help("Print markdown, or the parsed definitions as JSON"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write to a file instead of stdout, creating its parent folders"),
+                )
+                .arg(
+                    Arg::new("split")
+                        .long("split")
+                        .value_name("DIR")
+                        .conflicts_with_all(["file", "output"])
+                        .help("Write every definition file to DIR, e.g. renoise/midi.lua to DIR/renoise/midi.md"),
+                )
+                .arg(
+                    Arg::new("raw-descriptions")
+                        .long("raw-descriptions")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep Markdown in descriptions as written instead of escaping it"),
+                )
+                .arg(
+                    Arg::new("global-source-order")
+                        .long("global-source-order")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["file", "split"])
+                        .help("Print every definition in file order, then source order within each file"),
+                )
+                .about("Print the markdown generated for a single definition file")
+                .after_help("With --format json the --file is optional, and the whole workspace is printed without it."),
+        )
 }
 
 fn main() {
@@ -21,10 +102,26 @@ fn main() {
     
     let matches = make_app().get_matches();
 
-    let preprocessor = LuaCats::new();
+    let mut preprocessor = LuaCats::new();
+    if let Some(path) = matches.get_one::<String>("export-workspace") {
+        preprocessor = preprocessor.export_workspace(path);
+    }
 
     match matches.subcommand() {
         Some(("supports", subargs)) => handle_supports(&preprocessor, subargs),
+        Some(("assets", _)) => {
+            if let Err(e) = handle_assets() {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(("doctor", subargs)) => handle_doctor(subargs),
+        Some(("markdown", subargs)) => {
+            if let Err(e) = handle_markdown(subargs) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
         Some((cmd, _)) => eprintln!("unknown subcommand {}", cmd),
         None => {
             if let Err(e) = handle_preprocessing(&preprocessor) {
@@ -57,6 +154,14 @@ fn handle_preprocessing(pre: &dyn Preprocessor) -> Result<(), Error> {
     Ok(())
 }
 
+fn handle_assets() -> anyhow::Result<()> {
+    let ctx = RenderContext::from_json(io::stdin())?;
+    let written = assets::write(assets::html_output_dir(&ctx))?;
+    log::debug!("Wrote assets: {:?}", written);
+
+    Ok(())
+}
+
 fn handle_supports(pre: &dyn Preprocessor, sub_args: &ArgMatches) -> ! {
     let renderer = sub_args
         .get_one::<String>("renderer")
@@ -69,3 +174,85 @@ fn handle_supports(pre: &dyn Preprocessor, sub_args: &ArgMatches) -> ! {
         process::exit(1);
     }
 }
+
+fn handle_markdown(sub_args: &ArgMatches) -> anyhow::Result<()> {
+    let cwd = env::current_dir()?;
+    let definitions_path = cwd.join(
+        sub_args
+            .get_one::<String>("definitions-path")
+            .expect("Default argument"),
+    );
+    // Files are found relative to the definitions root, unless the path
+    // names a file from the current directory instead
+    let file = sub_args.get_one::<String>("file").map(|file| {
+        let from_cwd = cwd.join(file);
+        if !definitions_path.join(file).exists() && from_cwd.exists() {
+            from_cwd
+        } else {
+            PathBuf::from(file)
+        }
+    });
+    let format = sub_args.get_one::<String>("format").expect("Default argument");
+    if format == "json" && sub_args.contains_id("split") {
+        return Err(anyhow::anyhow!("--split only writes markdown, so it can't be used with --format json"));
+    }
+    let options = MarkdownOptions {
+        raw_descriptions: sub_args.get_flag("raw-descriptions"),
+        ..Default::default()
+    };
+
+    let mut workspace = Workspace::new(&definitions_path);
+    workspace.load(generate_docs(&definitions_path)?)?;
+    workspace.remove_hidden();
+
+    if let Some(dir) = sub_args.get_one::<String>("split") {
+        return workspace.write_markdown(cwd.join(dir), &options);
+    }
+
+    let output = if sub_args.get_flag("global-source-order") {
+        match format.as_str() {
+            "json" => serde_json::to_string_pretty(&workspace.definitions_in_source_order())?,
+            _ => workspace.render_markdown(&options)?,
+        }
+    } else {
+        match (format.as_str(), file) {
+            ("json", None) => serde_json::to_string_pretty(&workspace)?,
+            ("json", Some(file)) => {
+                let meta_file = workspace
+                    .find_file(&file)
+                    .ok_or_else(|| anyhow::anyhow!("No definitions were found in {}", file.display()))?;
+                serde_json::to_string_pretty(meta_file)?
+            }
+            (_, Some(file)) => workspace.render_file_markdown(file, &options)?,
+            (_, None) => return Err(anyhow::anyhow!("--file is required to print markdown")),
+        }
+    };
+
+    match sub_args.get_one::<String>("output") {
+        Some(path) => {
+            let path = cwd.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, output)?;
+        }
+        None => io::stdout().write_all(output.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+fn handle_doctor(sub_args: &ArgMatches) -> ! {
+    let definitions_path = sub_args
+        .get_one::<String>("definitions-path")
+        .expect("Default argument");
+    let report = Report::check(LUALS_COMMAND, definitions_path);
+
+    println!("{}", report);
+
+    if report.is_ready() {
+        process::exit(0);
+    } else {
+        process::exit(1);
+    }
+}
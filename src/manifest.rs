@@ -0,0 +1,229 @@
+//! A machine readable summary of the documented API, for tooling.
+//!
+//! Unlike LuaLS's doc.json, the manifest is grouped by kind of symbol and
+//! only records what readers see: names, types and descriptions.
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations,
+    lua_cats::{self, Definition, Extend, Kind},
+};
+
+/// The documented functions, classes and enums.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ApiManifest {
+    pub functions: Vec<Function>,
+    pub classes: Vec<Class>,
+    pub enums: Vec<Enum>,
+}
+
+impl ApiManifest {
+    /// Write the manifest to a file as pretty printed JSON.
+    pub fn export_json<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub name: String,
+    pub description: Option<String>,
+    pub params: Vec<Value>,
+    pub returns: Vec<Value>,
+    /// Any other signatures added with `---@overload`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overloads: Vec<Signature>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub params: Vec<Value>,
+    pub returns: Vec<Value>,
+}
+
+/// A parameter, return or field.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Value {
+    /// The name, `...` for variadics. Unnamed returns have none.
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub lua_type: String,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Class {
+    pub name: String,
+    pub description: Option<String>,
+    pub bases: Vec<String>,
+    pub fields: Vec<Value>,
+    pub methods: Vec<Function>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Enum {
+    pub name: String,
+    pub description: Option<String>,
+    pub members: Vec<EnumMember>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EnumMember {
+    pub name: String,
+    pub value: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Summarize the definitions' API. Aliases and plain variables aren't
+/// included.
+pub fn to_manifest(defs: &[Definition]) -> ApiManifest {
+    let mut manifest = ApiManifest::default();
+
+    for definition in defs {
+        let description = description(definition.rawdesc.as_deref());
+        match definition.kind() {
+            Kind::Function => {
+                if let Some(function) = function(&definition.name, description, &definition.signatures()) {
+                    manifest.functions.push(function);
+                }
+            }
+            Kind::Class => {
+                let mut class = Class {
+                    name: definition.name.clone(),
+                    description,
+                    bases: definition.bases().into_iter().map(str::to_owned).collect(),
+                    fields: Vec::new(),
+                    methods: Vec::new(),
+                };
+                for field in definition.fields.iter() {
+                    let field_description = self::description(field.rawdesc.as_deref());
+                    if field.is_method() {
+                        let name = field.qualified_name(&definition.name);
+                        class.methods.extend(function(&name, field_description, &field.signatures()));
+                    } else {
+                        class.fields.push(Value {
+                            name: Some(field.name.clone()),
+                            lua_type: field.view().unwrap_or("unknown").to_owned(),
+                            description: field_description,
+                        });
+                    }
+                }
+                manifest.classes.push(class);
+            }
+            Kind::Enum => manifest.enums.push(Enum {
+                name: definition.name.clone(),
+                description,
+                members: definition
+                    .enum_members()
+                    .into_iter()
+                    .map(|member| EnumMember {
+                        name: member.name,
+                        value: member.value,
+                        description: self::description(member.rawdesc.as_deref()),
+                    })
+                    .collect(),
+            }),
+            _ => {}
+        }
+    }
+
+    manifest
+}
+
+fn function(name: &str, description: Option<String>, signatures: &[&Extend]) -> Option<Function> {
+    let (first, overloads) = signatures.split_first()?;
+    let first = signature(first);
+
+    Some(Function {
+        name: name.to_owned(),
+        description,
+        params: first.params,
+        returns: first.returns,
+        overloads: overloads.iter().map(|extend| signature(extend)).collect(),
+    })
+}
+
+fn signature(extend: &Extend) -> Signature {
+    let params = extend
+        .args
+        .iter()
+        .map(|arg| {
+            let vararg = arg.is_vararg();
+            Value {
+                name: if vararg { Some("...".into()) } else { arg.name.clone() },
+                lua_type: if vararg { lua_cats::vararg_type(&arg.view) } else { &arg.view }.to_owned(),
+                description: description(arg.rawdesc.as_deref()),
+            }
+        })
+        .collect();
+    let returns = extend
+        .returns
+        .iter()
+        .map(|ret| {
+            let vararg = ret.is_vararg();
+            Value {
+                name: if vararg { Some("...".into()) } else { ret.name.clone() },
+                lua_type: if vararg { lua_cats::vararg_type(&ret.view) } else { &ret.view }.to_owned(),
+                description: description(ret.rawdesc.as_deref()),
+            }
+        })
+        .collect();
+
+    Signature { params, returns }
+}
+
+/// A description without its annotations, if there's anything left.
+fn description(rawdesc: Option<&str>) -> Option<String> {
+    rawdesc.map(annotations::strip).filter(|desc| !desc.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn function_manifest() -> anyhow::Result<()> {
+        let defs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "bit.band",
+            "type": "variable",
+            "rawdesc": "Bitwise and.\n@order 1",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setfield",
+                "file": "file:///bit.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function bit.band(x: integer, ...integer)\n  -> integer",
+                    "args": [
+                        {"name": "x", "type": "local", "view": "integer", "rawdesc": "The first.", "start": 1, "finish": 2},
+                        {"type": "...", "view": "integer...", "start": 3, "finish": 4}
+                    ],
+                    "returns": [{"name": "y", "type": "function.return", "view": "integer"}]
+                }
+            }]
+        }]"#)?;
+
+        let manifest = to_manifest(&defs);
+
+        assert_eq!(manifest.functions, vec![Function {
+            name: "bit.band".into(),
+            description: Some("Bitwise and.".into()),
+            params: vec![
+                Value { name: Some("x".into()), lua_type: "integer".into(), description: Some("The first.".into()) },
+                Value { name: Some("...".into()), lua_type: "integer".into(), description: None },
+            ],
+            returns: vec![Value { name: Some("y".into()), lua_type: "integer".into(), description: None }],
+            overloads: Vec::new(),
+        }]);
+        assert!(manifest.classes.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,3624 @@
+//! Markdown rendering using the embedded handlebars templates.
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use handlebars::{
+    handlebars_helper, no_escape, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+    RenderErrorReason,
+};
+use itertools::Itertools;
+use rust_embed::Embed;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    annotations, assets,
+    links::{self, SymbolIndex},
+    lua_cats::{self, Define, Definition, DefinitionType, Extend, Field, FuncArg, Kind},
+    workspace::MetaFile,
+};
+
+#[derive(Embed)]
+#[folder = "templates"]
+#[include = "*.hbs"]
+struct Assets;
+
+/// Options controlling how definitions are rendered.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct MarkdownOptions {
+    /// Where to look up the date shown in each chapter's footer, if at all.
+    pub last_updated: Option<LastUpdated>,
+    /// Render one line per symbol instead of a section per symbol.
+    pub compact: bool,
+    /// A URL the definitions root is published at, used to link each
+    /// definition to its source.
+    pub source_base_url: Option<String>,
+    /// A URL with `{file}` and `{line}` placeholders linked from each
+    /// definition's heading, like
+    /// `https://github.com/me/repo/blob/main/{file}#L{line}`.
+    pub source_url_template: Option<String>,
+    /// Nest definitions below sections built from their dotted names.
+    pub tree_from_names: bool,
+    /// Sort definitions by their `@order N` annotations.
+    pub sort_by_order: bool,
+    /// How class fields and methods are laid out, if not as a section each.
+    pub class_members_layout: Option<ClassMembersLayout>,
+    /// How function returns are laid out.
+    pub returns_layout: ReturnsLayout,
+    /// Fail on problems that are otherwise only warnings, like colliding anchors.
+    pub strict: bool,
+    /// How `@tag` labels are shown next to headings.
+    pub tag_style: TagStyle,
+    /// Target Markdown rendered directly by GitHub rather than mdbook, with
+    /// each module collapsed in a `<details>` section.
+    pub github_flavored: bool,
+    /// Reference the bundled CSS and JS at the top of each chapter. They're
+    /// written to the HTML output by the `mdbook-luacats assets` renderer.
+    pub assets: bool,
+    /// Follow class headings with a count of their fields and methods.
+    pub member_counts: bool,
+    /// Show the parameter names of functions with at most this many
+    /// parameters in their headings.
+    pub params_in_heading: Option<usize>,
+    /// Give each sub file folded into its parent's chapter a heading with its
+    /// module name, rather than merging the definitions seamlessly.
+    pub fold_heading: bool,
+    /// The icon shown before each kind of symbol's heading, keyed by the
+    /// kind's name. Kinds without an icon get none.
+    pub icons: BTreeMap<String, String>,
+    /// Show a one line signature of each function below its heading.
+    pub signature_summary: bool,
+    /// Break function signatures in code blocks over several lines, one
+    /// parameter per line.
+    pub detailed_signatures: bool,
+    /// List the members of field types that are unions of more than this
+    /// many types, instead of showing them inline.
+    pub union_list_threshold: Option<usize>,
+    /// Link the documented types in signatures. Links can't be placed in
+    /// code blocks, so signatures become plain HTML without highlighting.
+    pub link_signatures: bool,
+    /// The heading level of top level definitions, from 1 to 6. Defaults to
+    /// [`DEFAULT_HEADING_LEVEL`].
+    pub heading_level: Option<usize>,
+    /// The language code blocks are labelled with, for highlighting.
+    /// Defaults to `lua`, and an empty string leaves them unlabelled.
+    pub code_fence_language: Option<String>,
+    /// Move deprecated definitions and members after the rest.
+    pub deprecated_last: bool,
+    /// Build function signatures from their arguments and returns rather
+    /// than using LuaLS's view of them.
+    pub structured_signatures: bool,
+    /// Start each page with a list of links to its definitions, when it has
+    /// more than one.
+    pub emit_toc: bool,
+    /// Link argument descriptions that mention another argument, like
+    /// "greater than `min`", to that argument's row.
+    pub param_links: bool,
+    /// The locale to show descriptions in, where LuaLS exports them in
+    /// several. Descriptions not available in the locale use the default.
+    pub locale: Option<String>,
+    /// Group the definitions on each page by their kind, in this order, with
+    /// a heading before each group. Kinds missing from the order come after
+    /// the rest in [`DEFAULT_KIND_ORDER`].
+    pub group_by_kind: Option<Vec<Kind>>,
+    /// The order definitions are listed in on each page.
+    pub sort: Sort,
+    /// Insert descriptions as they're written, for annotations written in
+    /// Markdown, rather than escaping Markdown syntax in them.
+    pub raw_descriptions: bool,
+    /// Show signatures as inline code on one line instead of code blocks.
+    pub signatures_inline: bool,
+    /// Add an index of the sub chapters to chapters that have them.
+    pub emit_index: bool,
+    /// List a class's fields as a definition list rather than a table when
+    /// any has more than one paragraph or a code block.
+    pub field_definition_lists: bool,
+    /// Add a Required column to argument tables.
+    pub required_column: bool,
+    /// Emit an HTML comment with the symbol's name and anchor before each
+    /// heading, for external indexers.
+    pub permalink_comments: bool,
+    /// Only show an overload's description below its signature when it
+    /// differs from the description shared by the definition, rather than
+    /// repeating it for every overload.
+    pub dedupe_overload_descriptions: bool,
+    /// Render each definition's signature before its description.
+    pub signature_first: bool,
+    /// Show the size of each definition's source span.
+    pub show_size: bool,
+    /// Markdown shown in place of a missing description, like
+    /// `*No description.*`.
+    pub undocumented_placeholder: Option<String>,
+}
+
+/// The order definitions are grouped in by default.
+pub const DEFAULT_KIND_ORDER: [Kind; 5] = [Kind::Function, Kind::Class, Kind::Enum, Kind::Alias, Kind::Variable];
+
+/// The heading level of top level definitions, below the chapter's title.
+pub const DEFAULT_HEADING_LEVEL: usize = 2;
+
+impl MarkdownOptions {
+    /// The heading level of top level definitions.
+    pub fn heading_level(&self) -> usize {
+        self.heading_level.unwrap_or(DEFAULT_HEADING_LEVEL)
+    }
+
+    /// The language code blocks are labelled with.
+    pub fn code_fence_language(&self) -> &str {
+        self.code_fence_language.as_deref().unwrap_or("lua")
+    }
+}
+
+/// The icons used for each kind of symbol when icons are enabled without
+/// choosing them.
+pub fn default_icons() -> BTreeMap<String, String> {
+    Kind::ALL
+        .iter()
+        .map(|kind| {
+            let icon = match kind {
+                Kind::Alias => "🏷",
+                Kind::Class => "🏛",
+                Kind::Enum => "🔢",
+                Kind::Field => "🔹",
+                Kind::Function => "ƒ",
+                Kind::Method => "ƒ",
+                Kind::Variable => "📦",
+            };
+            (kind.name().to_owned(), icon.to_owned())
+        })
+        .collect()
+}
+
+/// The style `@tag` labels are rendered in.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagStyle {
+    /// A `<span class="luacats-tag">` element, to be styled by the book.
+    #[default]
+    Badge,
+    /// Inline code.
+    Code,
+    /// Bold text in brackets.
+    Bold,
+}
+
+/// The order definitions are rendered in.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+    /// The order of the definitions in the workspace.
+    #[default]
+    SourceOrder,
+    /// By name, then by where they're defined.
+    Alphabetical,
+}
+
+/// How a function's returns are laid out.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnsLayout {
+    /// A table with a row for each return.
+    #[default]
+    Table,
+    /// A list of `name: type — description` items.
+    Inline,
+}
+
+/// The layout of a class's fields and methods.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClassMembersLayout {
+    /// A single table of members with a column for their kind.
+    Combined,
+    /// A table of fields, followed by a section for the methods.
+    Split,
+    /// A table of fields linked by row, followed by each method.
+    Table,
+}
+
+/// The source of a chapter's "last updated" date.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LastUpdated {
+    /// The last commit touching the file, falling back to the mtime.
+    Git,
+    /// The file's modification time.
+    Mtime,
+}
+
+/// The data each file is rendered with.
+#[derive(Serialize)]
+struct FileContext<'a> {
+    /// The file path, relative to the workspace root.
+    path: &'a Path,
+    /// The file's definitions, in the order they should be rendered.
+    definitions: Vec<&'a Definition>,
+    options: &'a MarkdownOptions,
+    last_updated: Option<String>,
+    /// The path of each source file rendered in the chapter, by its URL as
+    /// used by `Define.file`.
+    sources: BTreeMap<String, String>,
+    /// The definitions nested by name, when `tree_from_names` is set.
+    tree: Vec<NameNode<'a>>,
+    /// The file's module name, e.g. `renoise.midi`.
+    module: String,
+    /// The HTML referencing the bundled assets, when `assets` is set.
+    assets: Option<String>,
+    /// The heading level of top level definitions.
+    level: usize,
+    /// The language code blocks are labelled with.
+    code_fence_language: &'a str,
+    /// The definitions grouped by kind, when `group_by_kind` is set.
+    groups: Vec<KindGroup<'a>>,
+    /// The other files defining the file's symbols, by symbol name.
+    also_defined_in: &'a BTreeMap<String, Vec<PathBuf>>,
+}
+
+/// Definitions of the same kind, rendered below a heading.
+#[derive(Debug, Serialize)]
+struct KindGroup<'a> {
+    /// The heading, e.g. `Functions`.
+    title: &'static str,
+    kind: &'static str,
+    definitions: Vec<&'a Definition>,
+}
+
+/// Partition definitions by kind in the given order, keeping the order of
+/// the definitions within each group.
+fn kind_groups<'a>(definitions: &[&'a Definition], order: &[Kind]) -> Vec<KindGroup<'a>> {
+    order
+        .iter()
+        .chain(DEFAULT_KIND_ORDER.iter())
+        .chain(Kind::ALL.iter())
+        .unique()
+        .filter_map(|kind| {
+            let definitions: Vec<&Definition> = definitions
+                .iter()
+                .copied()
+                .filter(|definition| definition.kind() == *kind)
+                .collect();
+            if definitions.is_empty() {
+                return None;
+            }
+
+            let title = match kind {
+                Kind::Alias => "Aliases",
+                Kind::Class => "Classes",
+                Kind::Enum => "Enums",
+                Kind::Field => "Fields",
+                Kind::Function => "Functions",
+                Kind::Method => "Methods",
+                Kind::Variable => "Variables",
+            };
+            Some(KindGroup { title, kind: kind.name(), definitions })
+        })
+        .collect()
+}
+
+/// A section of definitions sharing a dotted name prefix.
+#[derive(Debug, Serialize)]
+struct NameNode<'a> {
+    /// The dotted name of the section.
+    name: String,
+    /// The heading level of the section.
+    level: usize,
+    /// The definitions named exactly `name`.
+    definitions: Vec<&'a Definition>,
+    children: Vec<NameNode<'a>>,
+}
+
+impl<'a> NameNode<'a> {
+    fn new(name: String, level: usize) -> Self {
+        Self {
+            name,
+            level,
+            definitions: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Nest definitions by splitting their names on `.`, keeping their order.
+fn name_tree<'a>(definitions: &[&'a Definition], level: usize) -> Vec<NameNode<'a>> {
+    let mut roots: Vec<NameNode> = Vec::new();
+
+    for &definition in definitions {
+        let mut nodes = &mut roots;
+        let mut name = String::new();
+        let mut node_level = level;
+        let mut segments = definition.name.split('.').peekable();
+
+        while let Some(segment) = segments.next() {
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name.push_str(segment);
+
+            let index = match nodes.iter().position(|node| node.name == name) {
+                Some(index) => index,
+                None => {
+                    nodes.push(NameNode::new(name.clone(), node_level));
+                    nodes.len() - 1
+                }
+            };
+
+            if segments.peek().is_none() {
+                nodes[index].definitions.push(definition);
+                break;
+            }
+
+            nodes = &mut nodes[index].children;
+            node_level += 1;
+        }
+    }
+
+    roots
+}
+
+/// Render a file's definitions to Markdown.
+pub fn render_file(hbs: &Handlebars, file: &MetaFile, options: &MarkdownOptions) -> anyhow::Result<String> {
+    let last_updated = options
+        .last_updated
+        .and_then(|source| file.last_modified(source == LastUpdated::Git));
+
+    // Definitions merged in from sub files link to their own source
+    let sources = std::iter::once((&file.path, &file.source))
+        .chain(file.merged_sources.iter())
+        .filter_map(|(path, source)| {
+            let url = Url::from_file_path(source).ok()?;
+            Some((String::from(url), path.to_string_lossy().into_owned()))
+        })
+        .collect();
+
+    let mut definitions: Vec<&Definition> = file.definitions.iter().collect();
+    if options.sort == Sort::Alphabetical {
+        definitions.sort_by_key(|definition| {
+            let location = definition.defines.first().map(|define| (&define.file, define.start));
+            (&definition.name, location)
+        });
+    }
+    if options.sort_by_order {
+        // Ordered definitions first, then the rest alphabetically
+        definitions.sort_by(|a, b| match (a.order(), b.order()) {
+            (Some(a_order), Some(b_order)) => a_order.cmp(&b_order).then(a.name.cmp(&b.name)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.name.cmp(&b.name),
+        });
+    }
+    if options.deprecated_last {
+        definitions.sort_by_key(|definition| definition.deprecated);
+    }
+
+    let groups = match &options.group_by_kind {
+        Some(order) => kind_groups(&definitions, order),
+        None => Vec::new(),
+    };
+
+    let tree = if options.tree_from_names {
+        name_tree(&definitions, options.heading_level())
+    } else {
+        Vec::new()
+    };
+
+    let context = FileContext {
+        path: &file.path,
+        definitions,
+        options,
+        last_updated,
+        sources,
+        tree,
+        module: module_name(&file.path),
+        assets: options.assets.then(|| assets::references(&file.path)),
+        level: options.heading_level(),
+        code_fence_language: options.code_fence_language(),
+        groups,
+        also_defined_in: &file.also_defined_in,
+    };
+
+    let markdown = hbs.render("meta_file", &context)?;
+    check_anchors(&markdown, file, options)?;
+
+    let markdown = clamp_headings(&markdown);
+
+    if options.github_flavored {
+        return Ok(github_headings(&markdown));
+    }
+
+    Ok(markdown)
+}
+
+/// Render a glossary of the documented types (classes, aliases and enums) in
+/// the files and their sub files, alphabetically with a one line summary.
+///
+/// Links are relative to `page`, where the glossary chapter will be.
+pub fn render_glossary(files: &[MetaFile], symbols: &SymbolIndex, title: &str, page: &Path, locale: Option<&str>) -> String {
+    let mut types: Vec<&Definition> = Vec::new();
+    let mut pending: Vec<&MetaFile> = files.iter().collect();
+    while let Some(file) = pending.pop() {
+        types.extend(
+            file.definitions
+                .iter()
+                .filter(|definition| matches!(definition.kind(), Kind::Class | Kind::Alias | Kind::Enum)),
+        );
+        pending.extend(file.sub_files.iter());
+    }
+    types.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()).then(a.name.cmp(&b.name)));
+    types.dedup_by(|a, b| a.name == b.name);
+
+    let mut markdown = format!("# {}\n", title);
+    for definition in types {
+        let name = match symbols.link(&definition.name, page) {
+            Some(target) => format!("[`{}`]({})", definition.name, target),
+            None => format!("`{}`", definition.name),
+        };
+        let summary = definition
+            .description(locale)
+            .map(|desc| first_sentence(&annotations::strip(desc)))
+            .unwrap_or_default();
+
+        markdown.push_str(&format!("\n- {}", name));
+        if !summary.is_empty() {
+            markdown.push_str(&format!(" — {}", summary));
+        }
+    }
+    markdown.push('\n');
+
+    markdown
+}
+
+/// Render an overview of the modules, nested like their chapters, with the
+/// number of definitions each contains and totals for those with sub
+/// modules.
+///
+/// Files nested deeper than `nav_depth` chapters are linked in the chapter
+/// of the ancestor they're folded into.
+pub fn render_module_table<'a>(files: impl IntoIterator<Item = &'a MetaFile>, title: &str, nav_depth: Option<usize>, options: &MarkdownOptions) -> String {
+    fn count(count: usize) -> String {
+        match count {
+            1 => "1 definition".into(),
+            count => format!("{} definitions", count),
+        }
+    }
+
+    let mut markdown = format!("# {}\n\n", title);
+    let files: Vec<&MetaFile> = files.into_iter().collect();
+    let mut pending: Vec<(&MetaFile, usize, Option<String>)> = files.into_iter().rev().map(|file| (file, 1, None)).collect();
+    while let Some((file, depth, folded_into)) = pending.pop() {
+        let chapter = file.path.with_extension("md").to_string_lossy().replace('\\', "/");
+        let target = match &folded_into {
+            Some(ancestor) if options.fold_heading => format!("{}#module-{}", ancestor, anchor(&module_name(&file.path))),
+            Some(ancestor) => ancestor.clone(),
+            None => chapter.clone(),
+        };
+        markdown.push_str(&format!("{}- [{}]({}) — {}", "  ".repeat(depth - 1), file.title(), target, count(file.definitions.len())));
+        if !file.sub_files.is_empty() {
+            markdown.push_str(&format!(", {} in total", count(file.total_definitions())));
+        }
+        markdown.push('\n');
+
+        let fold = folded_into.is_some() || nav_depth.is_some_and(|nav_depth| depth >= nav_depth);
+        let sub_chapter = folded_into.or(fold.then_some(chapter));
+        pending.extend(file.sub_files.iter().rev().map(|sub_file| (sub_file, depth + 1, sub_chapter.clone())));
+    }
+
+    markdown
+}
+
+/// The data a file's index of sub files is rendered with.
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    level: usize,
+    entries: Vec<IndexEntry<'a>>,
+}
+
+/// A sub file in an index, with the symbols defined in it.
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    title: String,
+    /// The sub file's chapter, relative to the indexed chapter.
+    link: String,
+    /// The first definition of each symbol.
+    definitions: Vec<&'a Definition>,
+}
+
+/// Render an index of a file's sub files, linking each one's chapter and
+/// the symbols defined in it.
+pub fn render_index(hbs: &Handlebars, file: &MetaFile, options: &MarkdownOptions) -> anyhow::Result<String> {
+    let page = file.path.with_extension("md");
+    let entries = file
+        .sub_files
+        .iter()
+        .map(|sub_file| {
+            let target = sub_file.path.with_extension("md").to_string_lossy().replace('\\', "/");
+            IndexEntry {
+                title: sub_file.title(),
+                link: links::relative_link(&page, &target),
+                definitions: sub_file.definitions.iter().unique_by(|definition| &definition.name).collect(),
+            }
+        })
+        .collect();
+
+    let context = IndexContext {
+        level: options.heading_level(),
+        entries,
+    };
+
+    Ok(hbs.render("index", &context)?)
+}
+
+/// Render a file with its sub files folded in below its own definitions,
+/// for when they don't get chapters of their own.
+pub fn render_folded(hbs: &Handlebars, file: &MetaFile, options: &MarkdownOptions) -> anyhow::Result<String> {
+    let mut markdown = render_file(hbs, file, options)?;
+
+    // The assets are already referenced at the top of the chapter
+    let sub_options = MarkdownOptions {
+        assets: false,
+        ..options.clone()
+    };
+    let mut pending: Vec<&MetaFile> = file.sub_files.iter().rev().collect();
+    while let Some(sub_file) = pending.pop() {
+        if options.fold_heading {
+            // Modules are often named after the table they define, so the
+            // anchor is prefixed to keep it from colliding with the table's
+            let module = module_name(&sub_file.path);
+            markdown.push_str(&format!(
+                "\n\n{} {} {{ #module-{} }}",
+                heading(options.heading_level() as u64),
+                module,
+                anchor(&module)
+            ));
+        }
+        markdown.push_str("\n\n");
+        markdown.push_str(&render_file(hbs, sub_file, &sub_options)?);
+
+        pending.extend(sub_file.sub_files.iter().rev());
+    }
+
+    check_anchors(&markdown, file, options)?;
+
+    Ok(markdown)
+}
+
+/// Warn (or fail, in strict mode) when a file's chapter has duplicate anchors.
+fn check_anchors(markdown: &str, file: &MetaFile, options: &MarkdownOptions) -> anyhow::Result<()> {
+    let collisions = duplicate_anchors(markdown);
+    if collisions.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!(
+        "Duplicate anchors in {}: {}",
+        file.path.display(),
+        collisions.join(", ")
+    );
+    if options.strict {
+        return Err(anyhow!(message));
+    }
+    log::warn!("{}", message);
+
+    Ok(())
+}
+
+/// The module name of a file, e.g. `renoise.midi` for `renoise/midi.lua`.
+pub fn module_name(path: &Path) -> String {
+    path.with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .join(".")
+}
+
+/// Replace headings nested deeper than Markdown's six levels with bold text,
+/// keeping their anchors as HTML anchors.
+pub fn clamp_headings(markdown: &str) -> String {
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+
+    for line in markdown.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let Some(text) = line[level..].strip_prefix(' ').filter(|_| !in_fence && level > 6) else {
+            lines.push(line.to_owned());
+            continue;
+        };
+
+        let text = match text.rfind(" { ").filter(|_| text.ends_with('}')) {
+            Some(start) => &text[..start],
+            None => text,
+        };
+        match heading_id(line) {
+            Some(id) => lines.push(format!("<a id=\"{}\"></a>**{}**", id, text.trim())),
+            None => lines.push(format!("**{}**", text.trim())),
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Replace heading attributes, which GitHub doesn't support, with an HTML
+/// anchor before each heading.
+pub fn github_headings(markdown: &str) -> String {
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+
+    for line in markdown.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+
+        let attributes = line.rfind(" { ").filter(|_| !in_fence && line.starts_with('#') && line.ends_with('}'));
+        let Some(start) = attributes else {
+            lines.push(line.to_owned());
+            continue;
+        };
+
+        if let Some(id) = heading_id(line) {
+            lines.push(format!("<a id=\"{}\"></a>", id));
+            lines.push(String::new());
+        }
+        lines.push(line[..start].to_owned());
+    }
+
+    lines.join("\n")
+}
+
+/// The heading anchors used more than once within a page.
+///
+/// Symbols are given unique anchors by the [`SymbolIndex`], so these are
+/// symbols missing from the index or other headings colliding with them.
+pub fn duplicate_anchors(markdown: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut collisions = Vec::new();
+    let mut in_fence = false;
+
+    for line in markdown.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+
+        match heading_id(line) {
+            Some(id) if !in_fence && !seen.insert(id) && !collisions.iter().any(|c| c == id) => {
+                collisions.push(id.to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    collisions
+}
+
+/// The explicit `{ #id }` anchor of a heading line.
+fn heading_id(line: &str) -> Option<&str> {
+    if !line.starts_with('#') || !line.ends_with('}') {
+        return None;
+    }
+
+    let attributes = &line[line.rfind("{ #")? + 3..];
+
+    attributes.split_whitespace().next().filter(|id| *id != "}")
+}
+
+/// Compute the anchor used for a symbol's heading.
+///
+/// The name is lowercased, `.` and `:` separators (and whitespace) become
+/// `-`, and any other character that isn't alphanumeric, `-` or `_` is
+/// dropped. For example `renoise.Song:instrument()` becomes
+/// `renoise-song-instrument`. Links to a symbol should always use this
+/// rather than relying on the renderer's own slugging.
+pub fn anchor(name: &str) -> String {
+    name.chars()
+        .filter_map(|c| match c {
+            '.' | ':' | '-' => Some('-'),
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '_' => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Find `@cast`/`@as` type narrowing fragments in a description.
+///
+/// Each fragment runs from the tag to the end of its line, or to the end of
+/// the enclosing inline code span.
+pub fn type_narrowing_notes(desc: &str) -> Vec<String> {
+    desc.lines()
+        .filter_map(|line| {
+            let start = line
+                .match_indices('@')
+                .map(|(index, _)| index)
+                .find(|&index| {
+                    let tag = &line[index..];
+                    let boundary = !line[..index]
+                        .chars()
+                        .last()
+                        .is_some_and(char::is_alphanumeric);
+                    boundary && (tag.starts_with("@cast ") || tag.starts_with("@as "))
+                })?;
+            let fragment = line[start..].split('`').next().unwrap_or_default().trim();
+
+            Some(fragment.to_owned())
+        })
+        .collect()
+}
+
+/// The first sentence of a description, on a single line.
+pub fn first_sentence(desc: &str) -> String {
+    let paragraph = desc.split("\n\n").next().unwrap_or_default();
+    let paragraph = paragraph.split_whitespace().join(" ");
+
+    match paragraph.find(". ") {
+        Some(end) => paragraph[..=end].to_owned(),
+        None => paragraph,
+    }
+}
+
+/// A symbol's signature on a single line, falling back to its name.
+fn compact_signature(name: &str, extends: &[Extend]) -> String {
+    match extends.first() {
+        Some(extend) => extend.view.split_whitespace().join(" "),
+        None => name.to_owned(),
+    }
+}
+
+/// The labels for a description's `@tag` annotations, each preceded by a
+/// space so they can follow a heading.
+pub fn tag_labels(desc: &str, style: TagStyle) -> String {
+    annotations::tags(desc)
+        .into_iter()
+        .map(|tag| match style {
+            TagStyle::Badge => format!(" <span class=\"luacats-tag\">{}</span>", tag),
+            TagStyle::Code => format!(" `{}`", tag),
+            TagStyle::Bold => format!(" **[{}]**", tag),
+        })
+        .collect()
+}
+
+/// Collapse runs of spaces and tabs in a `view` to a single space, leaving
+/// string literals and each line's indentation alone.
+pub fn normalize_view(view: &str) -> String {
+    view.lines()
+        .map(|line| {
+            let code = line.trim_start();
+            let mut output = line[..line.len() - code.len()].replace('\t', "    ");
+            let mut quote = None;
+            let mut escaped = false;
+            let mut space = false;
+
+            for c in code.trim_end().chars() {
+                match quote {
+                    Some(q) => {
+                        output.push(c);
+                        if escaped {
+                            escaped = false;
+                        } else if c == '\\' {
+                            escaped = true;
+                        } else if c == q {
+                            quote = None;
+                        }
+                    }
+                    None if c == ' ' || c == '\t' => space = true,
+                    None => {
+                        if space {
+                            output.push(' ');
+                            space = false;
+                        }
+                        if c == '"' || c == '\'' {
+                            quote = Some(c);
+                        }
+                        output.push(c);
+                    }
+                }
+            }
+
+            output
+        })
+        .join("\n")
+}
+
+/// A summary like ` (2 fields, 1 method)` for a class heading, or nothing
+/// if the definition isn't a class.
+pub fn member_counts(definition: &Definition) -> String {
+    if !definition.is_class() {
+        return String::new();
+    }
+
+    let (methods, fields): (Vec<&Field>, Vec<&Field>) = definition.fields.iter().partition(|field| field.is_method());
+    let plural = |count: usize, noun: &str| match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    };
+
+    format!(" ({}, {})", plural(fields.len(), "field"), plural(methods.len(), "method"))
+}
+
+/// A parameter list like `(value, min, max)` to follow a function's name
+/// in its heading, if it has at most `max` parameters.
+///
+/// The implicit `self` of methods isn't counted.
+pub fn heading_params(extends: &[Extend], max: usize) -> String {
+    let Some(extend) = extends.first().filter(|extend| extend.lua_type == DefinitionType::Function) else {
+        return String::new();
+    };
+
+    let names: Vec<&str> = extend
+        .args
+        .iter()
+        .map(|arg| arg.name.as_deref().unwrap_or("..."))
+        .filter(|name| *name != "self")
+        .collect();
+    if names.len() > max {
+        return String::new();
+    }
+
+    format!("({})", names.join(", "))
+}
+
+/// Build a function's signature from its arguments and returns, in the
+/// same format as LuaLS' views, e.g. `function f(x: integer)\n  -> string`.
+///
+/// Methods named like `Class:method` leave out their `self` argument. Other
+/// types are shown as they are.
+pub fn structured_signature(name: &str, extend: &Extend) -> String {
+    signature_for(name, extend, name.contains(':'))
+}
+
+/// The signature for calling a callable class by its name, from its
+/// `__call` metamethod, whose first argument is the class itself.
+pub fn call_signature(name: &str, extend: &Extend) -> String {
+    let mut extend = extend.clone();
+    if extend.args.first().is_some_and(|arg| arg.name.as_deref() == Some("self")) {
+        extend.args.remove(0);
+    }
+
+    signature_for(name, &extend, false)
+}
+
+fn signature_for(name: &str, extend: &Extend, is_method: bool) -> String {
+    if extend.lua_type != DefinitionType::Function {
+        return extend.view.clone();
+    }
+
+    let args = extend
+        .args
+        .iter()
+        .filter(|arg| !(is_method && arg.name.as_deref() == Some("self")))
+        .map(|arg| {
+            if arg.is_vararg() {
+                format!("...: {}", lua_cats::vararg_type(&arg.view))
+            } else {
+                format!("{}: {}", arg.name.as_deref().unwrap_or_default(), arg.view)
+            }
+        })
+        .join(", ");
+
+    let mut signature = format!("function {}({})", name, args);
+    for (index, ret) in extend.returns.iter().enumerate() {
+        let marker = if index == 0 { "->".to_owned() } else { format!("{}.", index + 1) };
+        let ret = match ret.name.as_deref() {
+            _ if ret.is_vararg() => format!("...{}", lua_cats::vararg_type(&ret.view)),
+            Some(name) => format!("{}: {}", name, ret.view),
+            None => ret.view.clone(),
+        };
+        signature.push_str(&format!("\n  {} {}", marker, ret));
+    }
+
+    signature
+}
+
+/// A function argument or return, which are rendered the same way.
+#[derive(Deserialize)]
+struct Param {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    lua_type: DefinitionType,
+    view: String,
+}
+
+impl Param {
+    /// The name for the table, `...` for variadics.
+    fn name(&self) -> String {
+        if lua_cats::is_vararg(self.name.as_deref(), self.lua_type, &self.view) {
+            return "...".into();
+        }
+
+        self.name.as_deref().map(param_name).unwrap_or_default()
+    }
+
+    /// The type of the value, or of each value for variadics.
+    fn lua_type(&self) -> &str {
+        if lua_cats::is_vararg(self.name.as_deref(), self.lua_type, &self.view) {
+            return lua_cats::vararg_type(&self.view);
+        }
+
+        &self.view
+    }
+
+    /// Whether the argument can be left out, because it's variadic or its
+    /// type allows `nil`.
+    fn is_optional(&self) -> bool {
+        lua_cats::is_vararg(self.name.as_deref(), self.lua_type, &self.view)
+            || self.view.ends_with('?')
+            || self.view.split('|').any(|t| t.trim() == "nil")
+    }
+}
+
+/// The anchor of an argument's row in a function's Arguments table.
+fn param_anchor(symbol: &str, name: &str) -> String {
+    format!("{}-param-{}", anchor(symbol), anchor(name))
+}
+
+/// Link the inline code spans in an argument's description that name
+/// another of the function's arguments to that argument's row.
+pub fn param_links(desc: &str, symbol: &str, args: &[FuncArg]) -> String {
+    let mut output = String::new();
+    let mut rest = desc;
+
+    while let Some(start) = rest.find('`') {
+        let Some(len) = rest[start + 1..].find('`') else {
+            break;
+        };
+        let end = start + 1 + len;
+        let name = &rest[start + 1..end];
+
+        output.push_str(&rest[..start]);
+        if args.iter().any(|arg| arg.name.as_deref() == Some(name)) {
+            output.push_str(&format!("[`{}`](#{})", name, param_anchor(symbol, name)));
+        } else {
+            output.push_str(&rest[start..=end]);
+        }
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Lua's reserved words, which can't be used as names in real code.
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Render a parameter or return name for a table cell.
+///
+/// Names that are Lua keywords are shown as inline code so they read as
+/// names rather than prose, and any other Markdown syntax is escaped.
+pub fn param_name(name: &str) -> String {
+    if LUA_KEYWORDS.contains(&name) {
+        return format!("`{}`", name);
+    }
+
+    name.chars().fold(String::new(), |mut output, c| {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|') {
+            output.push('\\');
+        }
+        output.push(c);
+        output
+    })
+}
+
+/// A function's view with each parameter on its own line, for functions
+/// with more than one parameter.
+pub fn detailed_signature(view: &str) -> String {
+    let view = normalize_view(view);
+    let Some(open) = view.find('(') else {
+        return view;
+    };
+
+    let mut depth = 0;
+    let mut params = Vec::new();
+    let mut start = open + 1;
+    let mut close = None;
+    for (index, c) in view.char_indices().skip_while(|(index, _)| *index <= open) {
+        match c {
+            '(' | '<' | '{' | '[' => depth += 1,
+            ')' if depth == 0 => {
+                params.push(view[start..index].trim());
+                close = Some(index);
+                break;
+            }
+            ')' | '>' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(view[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let Some(close) = close.filter(|_| params.len() > 1) else {
+        return view;
+    };
+
+    format!(
+        "{}\n{}\n{}",
+        &view[..=open],
+        params.iter().map(|param| format!("  {},", param)).join("\n"),
+        &view[close..]
+    )
+}
+
+/// Make inline Markdown safe to place in a table cell.
+///
+/// Only pipes and line breaks are escaped so inline code, emphasis and links
+/// in the text still render.
+pub fn table_cell(text: &str) -> String {
+    text.trim()
+        .replace('|', "\\|")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+/// Render text as inline code on a single line, using enough backticks to
+/// wrap any it contains.
+pub fn inline_code(text: &str) -> String {
+    let code = normalize_view(text).split_whitespace().join(" ");
+    let longest_run = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run + 1);
+
+    if longest_run > 0 {
+        format!("{} {} {}", fence, code, fence)
+    } else {
+        format!("{}{}{}", fence, code, fence)
+    }
+}
+
+/// Link to a line of a file below the published definitions root.
+pub fn source_url(base_url: &str, path: &str, line: u64) -> String {
+    format!(
+        "{}/{}#L{}",
+        base_url.trim_end_matches('/'),
+        path.replace('\\', "/"),
+        line
+    )
+}
+
+/// Fill in a `source-url-template`'s `{file}` and `{line}` placeholders.
+pub fn source_url_from_template(template: &str, path: &str, line: u64) -> String {
+    template
+        .replace("{file}", &path.replace('\\', "/"))
+        .replace("{line}", &line.to_string())
+}
+
+/// A link to where the definition is defined in this chapter's source files,
+/// for its heading.
+fn source_link(defines: &[Define], sources: &serde_json::Map<String, serde_json::Value>, template: Option<&str>) -> String {
+    let Some(template) = template else {
+        return String::new();
+    };
+
+    defines
+        .iter()
+        .find_map(|define| Some((define, sources.get(&define.file)?.as_str()?)))
+        .map(|(define, path)| format!(" [source]({})", source_url_from_template(template, path, define.line())))
+        .unwrap_or_default()
+}
+
+/// The ATX heading marker for a level, e.g. `##`.
+fn heading(level: u64) -> String {
+    "#".repeat(level as usize)
+}
+
+/// The serialized fields passing `filter`, in source order.
+fn filter_fields(fields: &[serde_json::Value], filter: fn(&Field) -> bool, deprecated_last: bool) -> Vec<serde_json::Value> {
+    fields
+        .iter()
+        .filter_map(|value| Field::deserialize(value).ok().map(|field| (field, value)))
+        .filter(|(field, _)| filter(field))
+        .sorted_by_key(|(field, _)| (deprecated_last && field.deprecated, field.file.clone(), field.start))
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+handlebars_helper!(field_name_helper: |parent: str, name: str, lua_type: DefinitionType| lua_cats::qualified_name(parent, name, lua_type));
+handlebars_helper!(first_sentence_helper: |desc: str| first_sentence(desc));
+handlebars_helper!(compact_signature_helper: |name: str, extends: Vec<Extend>| compact_signature(name, &extends));
+handlebars_helper!(heading_helper: |level: u64, {offset: u64 = 0}| heading(level + offset));
+handlebars_helper!(description_helper: |item: Json, options: Json| item_description(item, options));
+handlebars_helper!(add_helper: |a: u64, b: u64| a + b);
+handlebars_helper!(is_method_helper: |field: Field| field.is_method());
+handlebars_helper!(methods_helper: |fields: array, {deprecated_last: bool = false}| filter_fields(fields, |field| field.is_method() && !field.is_operator(), deprecated_last));
+handlebars_helper!(properties_helper: |fields: array, {deprecated_last: bool = false}| filter_fields(fields, |field| !field.is_method() && !field.is_operator(), deprecated_last));
+handlebars_helper!(field_list_helper: |fields: array, options: Json| options.get("field_definition_lists").and_then(|enabled| enabled.as_bool()).unwrap_or_default() && fields
+    .iter()
+    .filter(|value| Field::deserialize(*value).is_ok_and(|field| !field.is_method() && !field.is_operator()))
+    .any(|value| is_rich_description(&item_description(value, options))));
+handlebars_helper!(call_signatures: |definition: Definition| definition.call_signatures().into_iter().map(|signature| serde_json::json!(signature)).collect::<Vec<_>>());
+handlebars_helper!(call_signature_helper: |name: str, extend: Extend| call_signature(name, &extend));
+handlebars_helper!(member_fields_helper: |fields: array, {deprecated_last: bool = false}| filter_fields(fields, |field| !field.is_operator(), deprecated_last));
+handlebars_helper!(operators_helper: |fields: array| filter_fields(fields, Field::is_operator, false));
+handlebars_helper!(is_operator_helper: |field: Field| field.is_operator());
+handlebars_helper!(field_type_helper: |field: Field| field.view().map(|view| view.split_whitespace().join(" ")).unwrap_or_else(|| "unknown".into()));
+handlebars_helper!(cell_helper: |text: Json| text.as_str().map(table_cell).unwrap_or_default());
+handlebars_helper!(line_helper: |position: u64| lua_cats::line(position));
+handlebars_helper!(span_size: |start: u64, finish: u64| lua_cats::span_size(start, finish));
+handlebars_helper!(source_link_helper: |defines: Vec<Define>, sources: object, template: Json| source_link(&defines, sources, template.as_str()));
+handlebars_helper!(source_path_helper: |file: str, sources: object| sources.get(file).and_then(|path| path.as_str()).unwrap_or_default().to_owned());
+handlebars_helper!(source_url_helper: |base_url: str, path: str, position: u64| source_url(base_url, path, lua_cats::line(position)));
+handlebars_helper!(type_params_helper: |extend: Extend| extend.type_params().into_iter().map(|param| serde_json::json!(param)).collect::<Vec<_>>());
+handlebars_helper!(tags_helper: |desc: Json, style: TagStyle| desc.as_str().map(|desc| tag_labels(desc, style)).unwrap_or_default());
+handlebars_helper!(signature_helper: |view: str| normalize_view(view));
+handlebars_helper!(param_name_helper: |param: Param| param.name());
+handlebars_helper!(param_type_helper: |param: Param| param.lua_type().to_owned());
+handlebars_helper!(also_defined_in: |name: str, files: object| files
+    .get(name)
+    .and_then(|paths| paths.as_array())
+    .map(|paths| paths.iter().filter_map(|path| path.as_str()).map(|path| format!("`{}`", path)).join(", "))
+    .unwrap_or_default());
+handlebars_helper!(param_required: |param: Param| if param.is_optional() { "No" } else { "Yes" });
+handlebars_helper!(member_counts_helper: |definition: Definition| member_counts(&definition));
+handlebars_helper!(heading_params_helper: |extends: Vec<Extend>, max: Json| max.as_u64().map(|max| heading_params(&extends, max as usize)).unwrap_or_default());
+handlebars_helper!(icon_helper: |kind: Kind, icons: object| icons.get(kind.name()).and_then(|icon| icon.as_str()).map(|icon| format!("{} ", icon)).unwrap_or_default());
+handlebars_helper!(definition_kind_helper: |definition: Definition| definition.kind().name());
+handlebars_helper!(field_kind_helper: |field: Field| field.kind().name());
+handlebars_helper!(detailed_signature_helper: |view: str| detailed_signature(view));
+handlebars_helper!(signature_summary_helper: |extends: Vec<Extend>| extends.first().filter(|extend| extend.lua_type == DefinitionType::Function).map(|extend| normalize_view(&extend.view).split_whitespace().join(" ")).unwrap_or_default());
+handlebars_helper!(signature_view_helper: |extend: Extend, name: str, {structured: bool = false}| if structured {
+    structured_signature(name, &extend)
+} else {
+    extend.view
+});
+handlebars_helper!(enum_members_helper: |definition: Definition| definition.enum_members().into_iter().map(|member| serde_json::json!(member)).collect::<Vec<_>>());
+handlebars_helper!(param_anchor_helper: |symbol: str, param: Param| param_anchor(symbol, &param.name.unwrap_or_else(|| "...".into())));
+handlebars_helper!(param_links_helper: |desc: Json, symbol: str, args: Vec<FuncArg>| desc.as_str().map(|desc| param_links(desc, symbol, &args)).unwrap_or_default());
+handlebars_helper!(inline_code_helper: |text: str| inline_code(text));
+handlebars_helper!(overload_description: |extend: Json, shared: str, options: Json| {
+    let description = item_description(extend, options);
+    let dedupe = options.get("dedupe_overload_descriptions").and_then(|dedupe| dedupe.as_bool()).unwrap_or_default();
+    if dedupe && description == shared {
+        String::new()
+    } else {
+        description
+    }
+});
+handlebars_helper!(overloads_helper: |item: Json| {
+    let signatures: Vec<Extend> = match Definition::deserialize(item) {
+        Ok(definition) => definition.signatures().into_iter().cloned().collect(),
+        Err(_) => Field::deserialize(item)
+            .map(|field| field.signatures().into_iter().cloned().collect())
+            .unwrap_or_default(),
+    };
+    if signatures.len() > 1 {
+        signatures.into_iter().map(|signature| serde_json::json!(signature)).collect()
+    } else {
+        Vec::new()
+    }
+});
+handlebars_helper!(signature_described_helper: |item: Json| signature_described(item));
+handlebars_helper!(is_field: |item: Json| Definition::deserialize(item).is_err() && Field::deserialize(item).is_ok());
+handlebars_helper!(alias_members_helper: |extend: Extend| {
+    let members = extend.union_members();
+    if members.len() > 1 {
+        members.into_iter().map(|member| serde_json::json!(member)).collect()
+    } else {
+        Vec::new()
+    }
+});
+handlebars_helper!(table_entries_helper: |extend: Extend| extend.table_entries().into_iter().map(|entry| serde_json::json!(entry)).collect::<Vec<_>>());
+handlebars_helper!(usages: |desc: Json| desc.as_str().map(annotations::usages).unwrap_or_default());
+handlebars_helper!(type_notes_helper: |desc: Json| desc.as_str().map(type_narrowing_notes).unwrap_or_default());
+
+/// Whether, with `signature_first`, a definition's or field's description
+/// follows its first signature. Tables and aliases listing their members
+/// have no signature to put first, so their description stays in place.
+fn signature_described(item: &serde_json::Value) -> bool {
+    let (extend, alias) = match Definition::deserialize(item) {
+        Ok(definition) => match definition.defines.into_iter().next() {
+            Some(define) => (define.extends.into_iter().next(), define.lua_type == DefinitionType::DocAlias),
+            None => (None, false),
+        },
+        Err(_) => (Field::deserialize(item).ok().and_then(|field| field.extends.into_iter().next()), false),
+    };
+
+    extend.is_some_and(|extend| extend.table_entries().is_empty() && !(alias && extend.union_members().len() > 1))
+}
+
+/// The file and start offset of a definition's first define, or a field's.
+fn item_location(item: &serde_json::Value) -> Option<(&str, u64)> {
+    let define = item.get("defines").and_then(|defines| defines.get(0)).unwrap_or(item);
+
+    Some((define.get("file")?.as_str()?, define.get("start")?.as_u64()?))
+}
+
+/// The description of a definition, field or another item with a `rawdesc`,
+/// in the given locale where available, without its annotations.
+fn item_description(item: &serde_json::Value, options: &serde_json::Value) -> String {
+    let rawdescs: BTreeMap<String, String> = item
+        .get("rawdescs")
+        .and_then(|rawdescs| serde_json::from_value(rawdescs.clone()).ok())
+        .unwrap_or_default();
+    let rawdesc = item.get("rawdesc").and_then(|rawdesc| rawdesc.as_str());
+    let locale = options.get("locale").and_then(|locale| locale.as_str());
+    let raw = options.get("raw_descriptions").and_then(|raw| raw.as_bool()).unwrap_or_default();
+
+    lua_cats::description(rawdesc, &rawdescs, locale)
+        .map(annotations::strip)
+        .map(|desc| if raw { desc } else { escape_markdown(&desc) })
+        .unwrap_or_default()
+}
+
+/// Whether a description has more than one paragraph or a code block, which
+/// don't fit in a table cell.
+fn is_rich_description(desc: &str) -> bool {
+    desc.contains("\n\n") || desc.contains("```")
+}
+
+/// Escape the Markdown syntax in text so it renders as written, leaving
+/// inline code and code blocks alone.
+pub fn escape_markdown(text: &str) -> String {
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+
+    for line in text.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            lines.push(line.to_owned());
+            continue;
+        }
+        if in_fence {
+            lines.push(line.to_owned());
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let text = &line[indent..];
+        let mut output = line[..indent].to_owned();
+        let mut in_code = false;
+        // Whether a list marker ending before `end` would start a list item
+        let is_marker = |end: usize| text[end..].chars().next().is_none_or(char::is_whitespace);
+        let mut chars = text.char_indices();
+        while let Some((index, c)) = chars.next() {
+            // Links and autolinks are kept as written
+            if !in_code {
+                let rest = &line[indent + index..];
+                if let Some(len) = link_len(rest).or_else(|| autolink_len(rest)) {
+                    output.push_str(&rest[..len]);
+                    // Skip the rest of the link after this character
+                    chars.nth(rest[..len].chars().count() - 2);
+                    continue;
+                }
+            }
+            match c {
+                '`' => in_code = !in_code,
+                '\\' | '*' | '_' | '[' | ']' | '<' if !in_code => output.push('\\'),
+                '#' | '>' if !in_code && index == 0 => output.push('\\'),
+                '-' | '+' if !in_code && index == 0 && is_marker(1) => output.push('\\'),
+                '.' | ')' if !in_code && (1..10).contains(&index) && text[..index].bytes().all(|b| b.is_ascii_digit()) && is_marker(index + 1) => output.push('\\'),
+                _ => {}
+            }
+            output.push(c);
+        }
+        lines.push(output);
+    }
+
+    lines.join("\n")
+}
+
+/// The length of the inline link `[text](destination)` at the start of
+/// `text`, if there is one.
+fn link_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix('[')?;
+    let label = rest.find(']')?;
+    if rest[..label].contains('[') {
+        return None;
+    }
+    let destination = rest[label + 1..].strip_prefix('(')?;
+    let close = destination.find(')')?;
+    if close == 0 {
+        return None;
+    }
+
+    Some(1 + label + 2 + close + 1)
+}
+
+/// The length of the autolink `<scheme:...>` at the start of `text`, if
+/// there is one.
+fn autolink_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix('<')?;
+    let close = rest.find('>')?;
+    let uri = &rest[..close];
+    let (scheme, _) = uri.split_once(':')?;
+    let valid_scheme = (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'));
+    if !valid_scheme || uri.contains(|c: char| c.is_whitespace() || c == '<') {
+        return None;
+    }
+
+    Some(close + 2)
+}
+
+/// A string parameter of a helper that's written by hand.
+fn str_param<'a>(h: &'a Helper, helper: &'static str, index: usize) -> Result<&'a str, RenderErrorReason> {
+    h.param(index)
+        .and_then(|v| v.value().as_str())
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex(helper, index))
+}
+
+/// Build the handlebars registry with the templates and helpers registered.
+///
+/// Types found in the symbol index are linked wherever they're rendered.
+pub fn registry(symbols: SymbolIndex) -> anyhow::Result<Handlebars<'static>> {
+    let mut hbs = Handlebars::new();
+    hbs.set_strict_mode(true);
+    hbs.register_embed_templates_with_extension::<Assets>(".hbs")?;
+    hbs.register_escape_fn(no_escape);
+    hbs.register_helper("field_name", Box::new(field_name_helper));
+    hbs.register_helper("type_notes", Box::new(type_notes_helper));
+    hbs.register_helper("usages", Box::new(usages));
+    hbs.register_helper("heading", Box::new(heading_helper));
+    hbs.register_helper("description", Box::new(description_helper));
+    hbs.register_helper("add", Box::new(add_helper));
+    hbs.register_helper("is_method", Box::new(is_method_helper));
+    hbs.register_helper("methods", Box::new(methods_helper));
+    hbs.register_helper("operators", Box::new(operators_helper));
+    hbs.register_helper("member_fields", Box::new(member_fields_helper));
+    hbs.register_helper("is_operator", Box::new(is_operator_helper));
+    hbs.register_helper("properties", Box::new(properties_helper));
+    hbs.register_helper("field_list", Box::new(field_list_helper));
+    hbs.register_helper("field_type", Box::new(field_type_helper));
+    hbs.register_helper("cell", Box::new(cell_helper));
+    hbs.register_helper("line", Box::new(line_helper));
+    hbs.register_helper("source_url", Box::new(source_url_helper));
+    hbs.register_helper("source_link", Box::new(source_link_helper));
+    hbs.register_helper("source_path", Box::new(source_path_helper));
+    hbs.register_helper("call_signatures", Box::new(call_signatures));
+    hbs.register_helper("call_signature", Box::new(call_signature_helper));
+    hbs.register_helper("span_size", Box::new(span_size));
+    hbs.register_helper("first_sentence", Box::new(first_sentence_helper));
+    hbs.register_helper("compact_signature", Box::new(compact_signature_helper));
+    hbs.register_helper("type_params", Box::new(type_params_helper));
+    hbs.register_helper("tags", Box::new(tags_helper));
+    hbs.register_helper("signature", Box::new(signature_helper));
+    hbs.register_helper("param_name", Box::new(param_name_helper));
+    hbs.register_helper("param_type", Box::new(param_type_helper));
+    hbs.register_helper("param_required", Box::new(param_required));
+    hbs.register_helper("also_defined_in", Box::new(also_defined_in));
+    hbs.register_helper("member_counts", Box::new(member_counts_helper));
+    hbs.register_helper("heading_params", Box::new(heading_params_helper));
+    hbs.register_helper("icon", Box::new(icon_helper));
+    hbs.register_helper("table_entries", Box::new(table_entries_helper));
+    hbs.register_helper("alias_members", Box::new(alias_members_helper));
+    hbs.register_helper("overloads", Box::new(overloads_helper));
+    hbs.register_helper("signature_described", Box::new(signature_described_helper));
+    hbs.register_helper("is_field", Box::new(is_field));
+    hbs.register_helper("overload_description", Box::new(overload_description));
+    hbs.register_helper("inline_code", Box::new(inline_code_helper));
+    hbs.register_helper("param_anchor", Box::new(param_anchor_helper));
+    hbs.register_helper("param_links", Box::new(param_links_helper));
+    hbs.register_helper("enum_members", Box::new(enum_members_helper));
+    hbs.register_helper("signature_view", Box::new(signature_view_helper));
+    hbs.register_helper("detailed_signature", Box::new(detailed_signature_helper));
+    hbs.register_helper("signature_summary", Box::new(signature_summary_helper));
+    hbs.register_helper("definition_kind", Box::new(definition_kind_helper));
+    hbs.register_helper("field_kind", Box::new(field_kind_helper));
+
+    let symbols = Arc::new(symbols);
+    let anchor_symbols = symbols.clone();
+    hbs.register_helper(
+        "anchor",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let name = str_param(h, "anchor", 0)?;
+
+                // Symbols are looked up by where they're defined, as their
+                // anchors are made unique when the index is built
+                match h.param(1).and_then(|item| item_location(item.value())) {
+                    Some((file, start)) => out.write(&anchor_symbols.anchor(name, file, start))?,
+                    None => out.write(&anchor(name))?,
+                }
+                Ok(())
+            },
+        ),
+    );
+    let type_link_symbols = symbols.clone();
+    hbs.register_helper(
+        "type_link",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let view = str_param(h, "type_link", 0)?;
+                let page = Path::new(str_param(h, "type_link", 1)?);
+                let list_over = h.hash_get("list_over").and_then(|v| v.value().as_u64());
+
+                let members = links::union_members(view);
+                match list_over {
+                    Some(max) if members.len() > max as usize => {
+                        let items: String = members
+                            .iter()
+                            .map(|member| format!("<li>{}</li>", type_link_symbols.link_types(member, page)))
+                            .collect();
+                        out.write(&format!("<ul>{}</ul>", items))?;
+                    }
+                    _ => out.write(&type_link_symbols.link_types(view, page))?,
+                }
+                Ok(())
+            },
+        ),
+    );
+    let signature_symbols = symbols.clone();
+    hbs.register_helper(
+        "signature_links",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let view = str_param(h, "signature_links", 0)?;
+                let page = str_param(h, "signature_links", 1)?;
+
+                out.write(&signature_symbols.link_signature(view, Path::new(page)))?;
+                Ok(())
+            },
+        ),
+    );
+    let span_symbols = symbols.clone();
+    hbs.register_helper(
+        "symbol_links",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let markdown = h.param(0).and_then(|v| v.value().as_str()).unwrap_or_default();
+                let page = str_param(h, "symbol_links", 1)?;
+
+                out.write(&span_symbols.link_code_spans(markdown, Path::new(page)))?;
+                Ok(())
+            },
+        ),
+    );
+    let subclass_symbols = symbols.clone();
+    hbs.register_helper(
+        "subclasses",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let class = str_param(h, "subclasses", 0)?;
+                let page = str_param(h, "subclasses", 1)?;
+
+                let tree = subclass_symbols.subclass_tree(class, Path::new(page));
+                if !tree.is_empty() {
+                    out.write(&format!("\n\n**Subclasses:**\n\n{}", tree.trim_end()))?;
+                }
+                Ok(())
+            },
+        ),
+    );
+    let see_symbols = symbols.clone();
+    hbs.register_helper(
+        "see_also",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let desc = h.param(0).and_then(|v| v.value().as_str()).unwrap_or_default();
+                let page = str_param(h, "see_also", 1)?;
+
+                let links: Vec<String> = annotations::see(desc)
+                    .into_iter()
+                    .map(|see| see_symbols.see_link(see, Path::new(page)))
+                    .collect();
+                if !links.is_empty() {
+                    out.write(&format!("\n\n**See also:** {}", links.join(", ")))?;
+                }
+                Ok(())
+            },
+        ),
+    );
+    hbs.register_helper(
+        "overrides",
+        Box::new(
+            move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+                let class = str_param(h, "overrides", 0)?;
+                let member = str_param(h, "overrides", 1)?;
+                let page = str_param(h, "overrides", 2)?;
+
+                let overridden = symbols.overridden(class, member).and_then(|name| {
+                    symbols
+                        .link(&name, Path::new(page))
+                        .map(|target| format!("\n\n*Overrides [`{}`]({})*", name, target))
+                });
+                if let Some(marker) = overridden {
+                    out.write(&marker)?;
+                }
+                Ok(())
+            },
+        ),
+    );
+
+    Ok(hbs)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use pulldown_cmark::{Event, Options, Parser, Tag};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn test_file(json: &str) -> MetaFile {
+        let definitions: Vec<Definition> = serde_json::from_str(json).unwrap();
+
+        MetaFile {
+            path: PathBuf::from("test.lua"),
+            definitions,
+            ..Default::default()
+        }
+    }
+
+    /// Render a file containing the given doc.json definitions.
+    fn render(json: &str) -> String {
+        render_with(json, &MarkdownOptions::default())
+    }
+
+    fn render_with(json: &str, options: &MarkdownOptions) -> String {
+        let file = test_file(json);
+        let symbols = SymbolIndex::from_files(std::slice::from_ref(&file));
+
+        render_file(&registry(symbols).unwrap(), &file, options).unwrap()
+    }
+
+    fn heading_ids(markdown: &str) -> Vec<String> {
+        Parser::new_ext(markdown, Options::ENABLE_HEADING_ATTRIBUTES)
+            .filter_map(|event| match event {
+                Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn anchor_normalization() {
+        assert_eq!(anchor("Class:method"), "class-method");
+        assert_eq!(anchor("renoise.Song:instrument()"), "renoise-song-instrument");
+        assert_eq!(anchor("snake_case"), "snake_case");
+        assert_eq!(anchor("renoise.song.instrument:name()"), "renoise-song-instrument-name");
+        assert_eq!(anchor("renoise.song.instrument.name"), anchor("renoise.song.instrument:name()"));
+    }
+
+    #[test]
+    fn method_anchor() {
+        let markdown = render(r#"[{
+            "name": "Class",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "method",
+                "type": "setmethod",
+                "start": 20,
+                "finish": 30,
+                "file": "file:///test.lua",
+                "extends": {"start": 20, "finish": 30, "type": "function", "view": "(method) Class:method()"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains("### Class:method { #class-method }"), "{}", markdown);
+
+        let link = format!("[Class:method](#{})", anchor("Class:method"));
+        assert_eq!(link, "[Class:method](#class-method)");
+        assert!(heading_ids(&markdown).contains(&"class-method".to_string()));
+    }
+
+    #[test]
+    fn type_narrowing_note() {
+        let markdown = render(r#"[{
+            "name": "get",
+            "type": "variable",
+            "rawdesc": "Get the current value.\nNarrow the result with `---@cast value string` when known.",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }]"#);
+
+        assert!(markdown.contains("> **Type narrowing:** `@cast value string`"), "{}", markdown);
+        assert_eq!(type_narrowing_notes("email@as or foo"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn last_updated_footer() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new("luacats-mtime")?;
+        let source = tmp_dir.path().join("test.lua");
+        fs::write(&source, "---@meta\n")?;
+
+        let mut file = test_file(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }]"#);
+        file.source = source.clone();
+
+        let options = MarkdownOptions {
+            last_updated: Some(LastUpdated::Mtime),
+            ..Default::default()
+        };
+        let markdown = render_file(&registry(SymbolIndex::new())?, &file, &options)?;
+
+        let mtime = fs::metadata(&source)?.modified()?;
+        let date = &humantime::format_rfc3339(mtime).to_string()[..10];
+        assert!(markdown.contains(&format!("*Last updated {}*", date)), "{}", markdown);
+
+        let markdown = render_file(&registry(SymbolIndex::new())?, &file, &MarkdownOptions::default())?;
+        assert!(!markdown.contains("Last updated"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_mode() {
+        let options = MarkdownOptions {
+            compact: true,
+            ..Default::default()
+        };
+        let markdown = render_with(r#"[
+            {
+                "name": "hello",
+                "type": "variable",
+                "rawdesc": "Say hello. Then return.",
+                "defines": [{
+                    "start": 0,
+                    "finish": 10,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello()\n  -> string"}
+                }]
+            },
+            {
+                "name": "greet",
+                "type": "variable",
+                "rawdesc": "Greet the person\nwith the given name.",
+                "defines": [{
+                    "start": 20,
+                    "finish": 30,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {"start": 20, "finish": 30, "type": "function", "view": "function greet(name: string)"}
+                }]
+            }
+        ]"#, &options);
+
+        assert_eq!(markdown.trim(), "- `function hello() -> string` — Say hello.\n- `function greet(name: string)` — Greet the person with the given name.");
+    }
+
+    #[test]
+    fn markdown_in_table_cells() {
+        let markdown = render(r#"[{
+            "name": "open",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function open(mode: string)",
+                    "args": [{
+                        "name": "mode",
+                        "type": "local",
+                        "view": "string",
+                        "rawdesc": "One of `r|w`, see [modes](x)\nfor details",
+                        "start": 14,
+                        "finish": 18
+                    }]
+                }
+            }]
+        }]"#);
+
+        assert!(markdown.contains("| mode | `string` | One of `r\\|w`, see [modes](x)<br>for details |"), "{}", markdown);
+
+        let html = mdbook::utils::render_markdown(&markdown, false);
+        assert!(html.contains("<code>r|w</code>"), "{}", html);
+        assert!(html.contains(r#"<a href="x">modes</a>"#), "{}", html);
+    }
+
+    #[test]
+    fn absolute_source_url() -> anyhow::Result<()> {
+        let mut file = test_file(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{"start": 90004, "finish": 90010, "type": "setglobal", "file": "file:///book/library/test.lua"}]
+        }]"#);
+        file.source = PathBuf::from("/book/library/test.lua");
+
+        let options = MarkdownOptions {
+            source_base_url: Some("https://github.com/me/repo/blob/main/library/".into()),
+            ..Default::default()
+        };
+        let markdown = render_file(&registry(SymbolIndex::new())?, &file, &options)?;
+
+        assert!(
+            markdown.contains("*Source: [test.lua:10](https://github.com/me/repo/blob/main/library/test.lua#L10)*"),
+            "{}",
+            markdown
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn source_url_template() -> anyhow::Result<()> {
+        let mut file = test_file(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{"start": 90004, "finish": 90010, "type": "setglobal", "file": "file:///book/library/renoise/test.lua"}]
+        }]"#);
+        file.path = PathBuf::from("renoise/test.lua");
+        file.source = PathBuf::from("/book/library/renoise/test.lua");
+        let hbs = registry(SymbolIndex::new())?;
+
+        let options = MarkdownOptions {
+            source_url_template: Some("https://gitlab.com/me/repo/-/blob/main/{file}#L{line}".into()),
+            ..Default::default()
+        };
+        let markdown = render_file(&hbs, &file, &options)?;
+        assert!(
+            markdown.starts_with("## hello [source](https://gitlab.com/me/repo/-/blob/main/renoise/test.lua#L10) { #hello }\n"),
+            "{}",
+            markdown
+        );
+
+        assert!(!render_file(&hbs, &file, &MarkdownOptions::default())?.contains("[source]"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tree_from_names() {
+        let options = MarkdownOptions {
+            tree_from_names: true,
+            ..Default::default()
+        };
+        let markdown = render_with(r#"[
+            {
+                "name": "a.b",
+                "type": "variable",
+                "defines": [{"start": 0, "finish": 10, "type": "setfield", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "a.c",
+                "type": "variable",
+                "defines": [{"start": 20, "finish": 30, "type": "setfield", "file": "file:///test.lua"}]
+            }
+        ]"#, &options);
+
+        let headings: Vec<&str> = markdown.lines().filter(|line| line.starts_with('#')).collect();
+
+        assert_eq!(headings, vec!["## a { #a }", "### a.b { #a-b }", "### a.c { #a-c }"]);
+    }
+
+    #[test]
+    fn self_is_not_linked() {
+        let markdown = render(r#"[
+            {
+                "name": "self",
+                "type": "type",
+                "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "Foo",
+                "type": "type",
+                "defines": [{"start": 20, "finish": 30, "type": "doc.class", "file": "file:///test.lua"}],
+                "fields": [{
+                    "name": "bar",
+                    "type": "setmethod",
+                    "start": 40,
+                    "finish": 50,
+                    "file": "file:///test.lua",
+                    "extends": {
+                        "start": 40,
+                        "finish": 50,
+                        "type": "function",
+                        "view": "(method) Foo:bar(other: Foo)",
+                        "args": [
+                            {"name": "self", "type": "self", "view": "self", "start": 40, "finish": 41},
+                            {"name": "other", "type": "local", "view": "Foo", "start": 42, "finish": 43}
+                        ]
+                    }
+                }]
+            }
+        ]"#);
+
+        assert!(markdown.contains("| self | `self` |"), "{}", markdown);
+        assert!(markdown.contains("| other | [`Foo`](#foo) |"), "{}", markdown);
+    }
+
+    #[test]
+    fn param_and_return_descriptions() {
+        let markdown = render(r#"[{
+            "name": "split",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function split(text: string)\n  -> string[]",
+                    "args": [
+                        {"name": "text", "type": "local", "view": "string", "rawdesc": "The text to split.\n@see string.gmatch", "start": 1, "finish": 2}
+                    ],
+                    "returns": [
+                        {"name": "parts", "type": "doc.type", "view": "string[]", "rawdesc": "The parts, like `a_b` or a_b.\n@tag pure"}
+                    ]
+                }
+            }]
+        }]"#);
+
+        assert!(markdown.contains("| text | `string` | The text to split. |"), "{}", markdown);
+        assert!(markdown.contains("| parts | `string[]` | The parts, like `a_b` or a\\_b. |"), "{}", markdown);
+        assert!(!markdown.contains("@see") && !markdown.contains("@tag"), "{}", markdown);
+    }
+
+    #[test]
+    fn generic_constraints() {
+        let markdown = render(r#"[
+            {
+                "name": "Base",
+                "type": "type",
+                "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "identity",
+                "type": "variable",
+                "defines": [{
+                    "start": 20,
+                    "finish": 30,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {
+                        "start": 20,
+                        "finish": 30,
+                        "type": "function",
+                        "view": "function identity(value: <T:Base>, keys: table<string>, other: <U>)\n  -> <T:Base>",
+                        "args": [
+                            {"name": "value", "type": "local", "view": "<T:Base>", "start": 21, "finish": 22}
+                        ]
+                    }
+                }]
+            }
+        ]"#);
+
+        assert!(markdown.contains("### Type Parameters { .h-type-params }"), "{}", markdown);
+        assert!(markdown.contains("| T | [`Base`](#base) |"), "{}", markdown);
+        assert!(markdown.contains("| U |  |"), "{}", markdown);
+        assert!(!markdown.contains("| string |"), "{}", markdown);
+    }
+
+    #[test]
+    fn tag_label_styles() {
+        let json = r#"[{
+            "name": "Foo",
+            "type": "type",
+            "rawdesc": "A foo.\n@tag experimental",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }]"#;
+
+        let markdown = render(json);
+        assert!(markdown.starts_with("## Foo <span class=\"luacats-tag\">experimental</span> { #foo }\n\nA foo.\n"), "{}", markdown);
+
+        let options = MarkdownOptions {
+            tag_style: TagStyle::Code,
+            ..Default::default()
+        };
+        let markdown = render_with(json, &options);
+        assert!(markdown.starts_with("## Foo `experimental` { #foo }"), "{}", markdown);
+    }
+
+    #[test]
+    fn normalized_view() {
+        assert_eq!(
+            normalize_view("function  foo(a:\tstring,  b: \"x  y\")\n  ->  integer  "),
+            "function foo(a: string, b: \"x  y\")\n  -> integer"
+        );
+        assert_eq!(normalize_view("'it\\'s  ok'  |  nil"), "'it\\'s  ok' | nil");
+    }
+
+    #[test]
+    fn github_flavored() {
+        let options = MarkdownOptions {
+            github_flavored: true,
+            ..Default::default()
+        };
+        let markdown = render_with(CLASS_WITH_MEMBERS, &options);
+
+        assert!(markdown.starts_with("<details>\n<summary><code>test</code></summary>\n\n"), "{}", markdown);
+        assert!(markdown.trim_end().ends_with("</details>"), "{}", markdown);
+        assert!(markdown.contains("<a id=\"point\"></a>\n\n## Point\n"), "{}", markdown);
+        assert!(!markdown.contains("{ #"), "{}", markdown);
+        assert!(!markdown.contains("{ ."), "{}", markdown);
+    }
+
+    #[test]
+    fn keyword_param_names() {
+        let markdown = render(r#"[{
+            "name": "foo",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function foo(end: integer, *x|y: string)",
+                    "args": [
+                        {"name": "end", "type": "local", "view": "integer", "start": 1, "finish": 2},
+                        {"name": "*x|y", "type": "local", "view": "string", "start": 3, "finish": 4}
+                    ]
+                }
+            }]
+        }]"#);
+
+        assert!(markdown.contains("| `end` | `integer` |"), "{}", markdown);
+        assert!(markdown.contains("| \\*x\\|y | `string` |"), "{}", markdown);
+    }
+
+    #[test]
+    fn asset_references() {
+        let options = MarkdownOptions {
+            assets: true,
+            ..Default::default()
+        };
+        let markdown = render_with(CLASS_WITH_MEMBERS, &options);
+
+        assert!(markdown.starts_with("<link rel=\"stylesheet\" href=\"luacats/luacats.css\">\n"), "{}", markdown);
+        assert!(markdown.contains("<script src=\"luacats/luacats.js\" defer></script>\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn class_member_counts() {
+        let options = MarkdownOptions {
+            member_counts: true,
+            ..Default::default()
+        };
+        let markdown = render_with(r#"[{
+            "name": "Point",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [
+                {"name": "x", "type": "doc.field", "start": 10, "finish": 20, "file": "file:///test.lua",
+                 "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "number"}},
+                {"name": "y", "type": "doc.field", "start": 20, "finish": 30, "file": "file:///test.lua",
+                 "extends": {"start": 20, "finish": 30, "type": "doc.type", "view": "number"}},
+                {"name": "length", "type": "setmethod", "start": 30, "finish": 40, "file": "file:///test.lua",
+                 "extends": {"start": 30, "finish": 40, "type": "function", "view": "(method) Point:length()"}}
+            ]
+        }]"#, &options);
+
+        assert!(markdown.starts_with("## Point (2 fields, 1 method) { #point }"), "{}", markdown);
+        assert!(!render(CLASS_WITH_MEMBERS).contains("field,"));
+    }
+
+    #[test]
+    fn overridden_method() {
+        let markdown = render(r#"[
+            {
+                "name": "Animal",
+                "type": "type",
+                "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+                "fields": [{
+                    "name": "speak",
+                    "type": "setmethod",
+                    "start": 10,
+                    "finish": 20,
+                    "file": "file:///test.lua",
+                    "extends": {"start": 10, "finish": 20, "type": "function", "view": "(method) Animal:speak()"}
+                }]
+            },
+            {
+                "name": "Dog",
+                "type": "type",
+                "defines": [{
+                    "start": 30,
+                    "finish": 40,
+                    "type": "doc.class",
+                    "file": "file:///test.lua",
+                    "extends": [{"start": 35, "finish": 40, "type": "doc.extends.name", "view": "Animal"}]
+                }],
+                "fields": [{
+                    "name": "speak",
+                    "type": "setmethod",
+                    "start": 50,
+                    "finish": 60,
+                    "file": "file:///test.lua",
+                    "extends": {"start": 50, "finish": 60, "type": "function", "view": "(method) Dog:speak()"}
+                }]
+            }
+        ]"#);
+
+        assert!(markdown.contains("### Dog:speak { #dog-speak }\n\n*Overrides [`Animal:speak`](#animal-speak)*\n"), "{}", markdown);
+        assert!(!markdown.contains("### Animal:speak { #animal-speak }\n\n*Overrides"), "{}", markdown);
+    }
+
+    #[test]
+    fn params_in_heading() {
+        let options = MarkdownOptions {
+            params_in_heading: Some(3),
+            ..Default::default()
+        };
+        let function = |name: &str, args: &[&str]| {
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| format!(r#"{{"name": "{}", "type": "local", "view": "number", "start": 1, "finish": 2}}"#, arg))
+                .collect();
+            format!(
+                r#"{{"name": "{0}", "type": "variable", "defines": [{{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua",
+                    "extends": {{"start": 0, "finish": 10, "type": "function", "view": "function {0}()", "args": [{1}]}}}}]}}"#,
+                name,
+                args.join(", ")
+            )
+        };
+        let json = format!("[{}, {}]", function("clamp", &["value", "min", "max"]), function("rect", &["x", "y", "w", "h"]));
+
+        let markdown = render_with(&json, &options);
+
+        assert!(markdown.contains("## clamp(value, min, max) { #clamp }"), "{}", markdown);
+        assert!(markdown.contains("## rect { #rect }"), "{}", markdown);
+    }
+
+    #[test]
+    fn variadic_args_and_returns() {
+        let markdown = render(r#"[{
+            "name": "pack",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function pack(...string)\n  -> ...string",
+                    "args": [{"type": "...", "view": "string", "start": 1, "finish": 2}],
+                    "returns": [{"name": "...", "type": "function.return", "view": "string"}]
+                }
+            }]
+        }]"#);
+
+        let rows: Vec<&str> = markdown.lines().filter(|line| line.starts_with("| ... |")).collect();
+        assert_eq!(rows, vec!["| ... | `string` |  |", "| ... | `string` |  |"], "{}", markdown);
+    }
+
+    #[test]
+    fn fold_heading() {
+        let mut file = test_file(r#"[{
+            "name": "renoise",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///renoise.lua"}]
+        }]"#);
+        file.path = PathBuf::from("renoise.lua");
+        file.sub_files.push(MetaFile {
+            path: PathBuf::from("renoise/midi.lua"),
+            depth: 1,
+            ..test_file(r#"[{
+                "name": "renoise.midi",
+                "type": "variable",
+                "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///renoise/midi.lua"}]
+            }]"#)
+        });
+        let hbs = registry(SymbolIndex::from_files(std::slice::from_ref(&file))).unwrap();
+
+        let seamless = render_folded(&hbs, &file, &MarkdownOptions::default()).unwrap();
+        assert_eq!(heading_ids(&seamless), vec!["renoise", "renoise-midi"]);
+
+        let options = MarkdownOptions {
+            fold_heading: true,
+            ..Default::default()
+        };
+        let headed = render_folded(&hbs, &file, &options).unwrap();
+        assert!(headed.contains("## renoise.midi { #module-renoise-midi }\n\n## renoise.midi { #renoise-midi }"), "{}", headed);
+    }
+
+    #[test]
+    fn folded_duplicate_anchors() {
+        let mut file = test_file(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///renoise.lua"}]
+        }]"#);
+        file.path = PathBuf::from("renoise.lua");
+        file.sub_files.push(MetaFile {
+            path: PathBuf::from("renoise/midi.lua"),
+            depth: 1,
+            ..test_file(r#"[{
+                "name": "hello",
+                "type": "variable",
+                "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///renoise/midi.lua"}]
+            }]"#)
+        });
+        let hbs = registry(SymbolIndex::new()).unwrap();
+
+        assert!(render_folded(&hbs, &file, &MarkdownOptions::default()).is_ok());
+
+        let options = MarkdownOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let result = render_folded(&hbs, &file, &options);
+        assert!(result.unwrap_err().to_string().contains("hello"));
+    }
+
+    #[test]
+    fn see_also_url() {
+        let markdown = render(r#"[{
+            "name": "Foo",
+            "type": "type",
+            "rawdesc": "A foo.\n@see https://example.com",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }]"#);
+
+        assert!(markdown.contains("A foo.\n\n**See also:** [https://example.com](https://example.com)\n"), "{}", markdown);
+        assert!(!markdown.contains("@see"), "{}", markdown);
+    }
+
+    #[test]
+    fn heading_level() {
+        let json = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function hello(x: integer)",
+                    "args": [{"name": "x", "type": "local", "view": "integer", "start": 1, "finish": 2}]
+                }
+            }]
+        }]"#;
+
+        let markdown = render_with(json, &MarkdownOptions {
+            heading_level: Some(1),
+            ..Default::default()
+        });
+        assert!(markdown.contains("# hello { #hello }\n"), "{}", markdown);
+        assert!(markdown.contains("\n## Arguments { .h-args }"), "{}", markdown);
+
+        assert!(render(json).contains("\n### Arguments { .h-args }"));
+    }
+
+    #[test]
+    fn code_fence_language() {
+        let json = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello()"}
+            }]
+        }]"#;
+
+        assert!(render(json).contains("```lua\nfunction hello()\n```"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            code_fence_language: Some("luau".into()),
+            ..Default::default()
+        });
+        assert!(markdown.contains("```luau\nfunction hello()\n```"), "{}", markdown);
+
+        let markdown = render_with(json, &MarkdownOptions {
+            code_fence_language: Some(String::new()),
+            ..Default::default()
+        });
+        assert!(markdown.contains("```\nfunction hello()\n```"), "{}", markdown);
+    }
+
+    #[test]
+    fn description_symbol_links() {
+        let markdown = render(r#"[{
+            "name": "Point",
+            "type": "type",
+            "rawdesc": "A point, see `Vector` and `other`.",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "origin",
+                "type": "doc.field",
+                "rawdesc": "Relative to `Vector`.",
+                "start": 0,
+                "finish": 10,
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "integer"}
+            }]
+        }, {
+            "name": "Vector",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }]"#);
+
+        assert!(markdown.contains("A point, see [`Vector`](#vector) and `other`."), "{}", markdown);
+        assert!(markdown.contains("\n\nRelative to [`Vector`](#vector).\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn deprecated_definitions() {
+        let json = r#"[{
+            "name": "old",
+            "type": "variable",
+            "deprecated": true,
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }, {
+            "name": "Point",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "x",
+                "type": "doc.field",
+                "deprecated": true,
+                "rawdesc": "Use `y`.",
+                "start": 0,
+                "finish": 10,
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "integer"}
+            }, {
+                "name": "y",
+                "type": "doc.field",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "integer"}
+            }]
+        }]"#;
+
+        let markdown = render(json);
+        assert!(markdown.contains("## old { #old }\n\n> **Deprecated**\n"), "{}", markdown);
+        assert!(markdown.contains("### Point.x { #point-x }\n\n> **Deprecated**\n"), "{}", markdown);
+        assert!(markdown.find("## old").unwrap() < markdown.find("## Point").unwrap());
+
+        let markdown = render_with(json, &MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Table),
+            ..Default::default()
+        });
+        assert!(markdown.contains("\n| <a id=\"point-x\"></a>x | `integer` | **Deprecated** Use `y`. |\n"), "{}", markdown);
+
+        let markdown = render_with(json, &MarkdownOptions {
+            deprecated_last: true,
+            ..Default::default()
+        });
+        assert!(markdown.find("## old").unwrap() > markdown.find("## Point").unwrap(), "{}", markdown);
+        assert!(markdown.find("point-x").unwrap() > markdown.find("point-y").unwrap(), "{}", markdown);
+    }
+
+    #[test]
+    fn alias_unions() {
+        let markdown = render(r#"[{
+            "name": "Color",
+            "type": "type",
+            "rawdesc": "A color.",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "doc.alias",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "doc.type",
+                    "view": "\"red\"|\"green\"|Rgb",
+                    "types": [
+                        {"view": "\"red\"", "rawdesc": "The color red"},
+                        {"view": "\"green\""},
+                        {"view": "Rgb", "rawdesc": "A custom color"}
+                    ]
+                }
+            }]
+        }, {
+            "name": "Rgb",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }, {
+            "name": "Size",
+            "type": "type",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "doc.alias",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "integer|\"auto\""}
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "A color.\n\n",
+            "- `\"red\"` — The color red\n",
+            "- `\"green\"`\n",
+            "- [`Rgb`](#rgb) — A custom color\n",
+        )), "{}", markdown);
+        assert!(markdown.contains("- `integer`\n- `\"auto\"`\n"), "{}", markdown);
+        assert!(!markdown.contains("```lua"), "{}", markdown);
+    }
+
+    #[test]
+    fn class_operators() {
+        let markdown = render(r#"[{
+            "name": "Vector",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "x",
+                "type": "doc.field",
+                "start": 0,
+                "finish": 10,
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "number"}
+            }, {
+                "name": "add",
+                "type": "doc.operator",
+                "rawdesc": "Add two vectors.",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "fun(self: Vector, other: Vector): Vector"}
+            }, {
+                "name": "__eq",
+                "type": "setmethod",
+                "start": 20,
+                "finish": 30,
+                "file": "file:///test.lua",
+                "extends": {"start": 20, "finish": 30, "type": "function", "view": "(method) Vector:__eq(other: Vector)\n  -> boolean"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "### Operators { .h-operators }\n\n",
+            "| Operator | Type | Description |\n",
+            "| -------- | ---- | ----------- |\n",
+            "| <a id=\"vector-add\"></a>`add` | `fun(self: `[`Vector`](#vector)`, other: `[`Vector`](#vector)`): `[`Vector`](#vector) | Add two vectors. |\n",
+            "| <a id=\"vector-__eq\"></a>`__eq` | `(method) `[`Vector`](#vector)`:__eq(other: `[`Vector`](#vector)`) -> boolean` |  |\n",
+        )), "{}", markdown);
+        assert!(!markdown.contains("### Vector:__eq"), "{}", markdown);
+    }
+
+    #[test]
+    fn structured_signatures() {
+        let json = r#"[{
+            "name": "Song",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "find",
+                "type": "setmethod",
+                "start": 0,
+                "finish": 10,
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "(method) Song:find(name: any)",
+                    "args": [
+                        {"name": "self", "type": "self", "view": "Song", "start": 1, "finish": 2},
+                        {"name": "name", "type": "local", "view": "string", "start": 1, "finish": 2},
+                        {"type": "...", "view": "integer...", "start": 3, "finish": 4}
+                    ],
+                    "returns": [
+                        {"name": "index", "type": "function.return", "view": "integer"},
+                        {"type": "function.return", "view": "string|nil"}
+                    ]
+                }
+            }]
+        }]"#;
+
+        assert!(render(json).contains("```lua\n(method) Song:find(name: any)\n```"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            structured_signatures: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains(
+            "```lua\nfunction Song:find(name: string, ...: integer)\n  -> index: integer\n  2. string|nil\n```"
+        ), "{}", markdown);
+    }
+
+    #[test]
+    fn enum_members() {
+        let markdown = render(r#"[{
+            "name": "Mode",
+            "type": "type",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "doc.enum",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "table", "view": "{\n    Fast: integer = 1,\n    Slow: integer = 2,\n}"}
+            }],
+            "fields": [{
+                "name": "Slow",
+                "type": "tablefield",
+                "rawdesc": "Takes `Mode.Fast` times longer.",
+                "start": 20,
+                "finish": 30,
+                "file": "file:///test.lua",
+                "extends": {"start": 20, "finish": 30, "type": "integer", "view": "integer"}
+            }, {
+                "name": "Fast",
+                "type": "tablefield",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 10, "finish": 20, "type": "integer", "view": "integer"}
+            }, {
+                "name": "Other",
+                "type": "tablefield",
+                "start": 30,
+                "finish": 40,
+                "file": "file:///test.lua",
+                "extends": {"start": 30, "finish": 40, "type": "string", "view": "\"other\""}
+            }]
+        }, {
+            "name": "Empty",
+            "type": "type",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "doc.enum",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "doc.type", "view": "Empty"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "### Members { .h-members }\n\n",
+            "| Name | Value | Description |\n",
+            "| ---- | ----- | ----------- |\n",
+            "| <a id=\"mode-fast\"></a>Fast | `1` |  |\n",
+            "| <a id=\"mode-slow\"></a>Slow | `2` | Takes [`Mode.Fast`](#mode-fast) times longer. |\n",
+            "| <a id=\"mode-other\"></a>Other | `\"other\"` |  |\n",
+        )), "{}", markdown);
+        assert!(!markdown.contains("### Fields"), "{}", markdown);
+        assert!(markdown.contains("## Empty { #empty }"), "{}", markdown);
+        assert!(markdown.contains("```lua\nEmpty\n```"), "{}", markdown);
+    }
+
+    #[test]
+    fn enum_member_descriptions() {
+        let markdown = render(r#"[{
+            "name": "Color",
+            "type": "type",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "doc.enum",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "table", "view": "{\n    Red: integer = 1,\n    Blue: integer = 2,\n}"}
+            }],
+            "fields": [{
+                "name": "Red",
+                "type": "tablefield",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 10, "finish": 20, "type": "integer", "view": "integer"}
+            }, {
+                "name": "Red",
+                "type": "doc.field",
+                "rawdesc": "The color of fire.",
+                "start": 15,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 15, "finish": 20, "type": "integer", "view": "integer"}
+            }, {
+                "name": "Blue",
+                "type": "tablefield",
+                "desc": "The color of water.",
+                "start": 20,
+                "finish": 30,
+                "file": "file:///test.lua",
+                "extends": {"start": 20, "finish": 30, "type": "integer", "view": "integer"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "| <a id=\"color-red\"></a>Red | `1` | The color of fire. |\n",
+            "| <a id=\"color-blue\"></a>Blue | `2` | The color of water. |\n",
+        )), "{}", markdown);
+    }
+
+    #[test]
+    fn returns_layout() {
+        let json = r#"[{
+            "name": "divmod",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function divmod(a: integer, b: integer)\n  -> quotient: integer\n  2. integer",
+                    "returns": [
+                        {"name": "quotient", "type": "function.return", "view": "integer", "rawdesc": "The whole part."},
+                        {"type": "function.return", "view": "integer", "rawdesc": "The remainder."}
+                    ]
+                }
+            }]
+        }]"#;
+
+        let table = render(json);
+        assert!(table.contains(concat!(
+            "| # | Name | Type | Description |\n",
+            "| - | ---- | ---- | ----------- |\n",
+            "| 1 | quotient | `integer` | The whole part. |\n",
+            "| 2 |  | `integer` | The remainder. |\n",
+        )), "{}", table);
+
+        let inline = render_with(json, &MarkdownOptions {
+            returns_layout: ReturnsLayout::Inline,
+            ..Default::default()
+        });
+        assert!(inline.contains(concat!(
+            "### Returns { .h-returns }\n\n",
+            "- `quotient`: `integer` — The whole part.\n",
+            "- `integer` — The remainder.\n",
+        )), "{}", inline);
+        assert!(!inline.contains("| # |"), "{}", inline);
+    }
+
+    #[test]
+    fn callable_classes() {
+        let markdown = render(r#"[{
+            "name": "Vector",
+            "type": "type",
+            "rawdesc": "A vector.",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "x",
+                "type": "doc.field",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "number"}
+            }, {
+                "name": "__call",
+                "type": "setmethod",
+                "start": 20,
+                "finish": 30,
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 20,
+                    "finish": 30,
+                    "type": "function",
+                    "view": "function Vector:__call(x: number)\n  -> Vector",
+                    "args": [
+                        {"name": "self", "type": "self", "view": "Vector", "start": 20, "finish": 21},
+                        {"name": "x", "type": "local", "view": "number", "start": 22, "finish": 23}
+                    ],
+                    "returns": [{"type": "function.return", "view": "Vector"}]
+                }
+            }]
+        }]"#);
+
+        let call = markdown.find("### Call { .h-call }\n\n```lua\nfunction Vector(x: number)\n  -> Vector\n```\n").expect(&markdown);
+        let fields = markdown.find("### Vector.x { #vector-x }").expect(&markdown);
+        assert!(markdown.find("A vector.").unwrap() < call);
+        assert!(call < fields, "{}", markdown);
+    }
+
+    #[test]
+    fn table_of_contents() {
+        let definition = |name: &str| format!(r#"{{
+            "name": "{}",
+            "type": "variable",
+            "defines": [{{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}}]
+        }}"#, name);
+        let options = MarkdownOptions {
+            emit_toc: true,
+            heading_level: Some(3),
+            ..Default::default()
+        };
+
+        let markdown = render_with(&format!("[{}, {}]", definition("hello"), definition("a.b")), &options);
+        assert!(markdown.starts_with("- [hello](#hello)\n- [a.b](#a-b)\n\n### hello { #hello }"), "{}", markdown);
+
+        let markdown = render_with(&format!("[{}]", definition("hello")), &options);
+        assert!(markdown.starts_with("### hello { #hello }"), "{}", markdown);
+    }
+
+    #[test]
+    fn param_cross_links() {
+        let json = r#"[{
+            "name": "math.clamp",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setfield",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function math.clamp(min: number, max: number)",
+                    "args": [
+                        {"name": "min", "type": "local", "view": "number", "start": 1, "finish": 2},
+                        {"name": "max", "type": "local", "view": "number", "rawdesc": "Greater than `min`, not `math.huge`.", "start": 3, "finish": 4}
+                    ]
+                }
+            }]
+        }]"#;
+
+        assert!(render(json).contains("| max | `number` | Greater than `min`, not `math.huge`. |"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            param_links: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains("| <a id=\"math-clamp-param-min\"></a>min | `number` |  |\n"), "{}", markdown);
+        assert!(markdown.contains(
+            "| <a id=\"math-clamp-param-max\"></a>max | `number` | Greater than [`min`](#math-clamp-param-min), not `math.huge`. |\n"
+        ), "{}", markdown);
+    }
+
+    #[test]
+    fn localized_descriptions() {
+        let json = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.",
+            "rawdescs": {"zh-cn": "打招呼。"},
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }]"#;
+        let with_locale = |locale: &str| MarkdownOptions {
+            locale: Some(locale.into()),
+            ..Default::default()
+        };
+
+        assert!(render_with(json, &with_locale("zh-cn")).contains("## hello { #hello }\n\n打招呼。"));
+        assert!(render_with(json, &with_locale("de")).contains("## hello { #hello }\n\nSay hello."));
+        assert!(render(json).contains("## hello { #hello }\n\nSay hello."));
+    }
+
+    #[test]
+    fn group_by_kind() {
+        let json = r#"[{
+            "name": "Point",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }, {
+            "name": "count",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }, {
+            "name": "hello",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello()"}
+            }]
+        }, {
+            "name": "Line",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+        }]"#;
+
+        let headings = |options: &MarkdownOptions| -> Vec<String> {
+            render_with(json, options)
+                .lines()
+                .filter(|line| line.starts_with('#'))
+                .map(str::to_owned)
+                .collect()
+        };
+
+        assert_eq!(headings(&MarkdownOptions::default()), vec![
+            "## Point { #point }",
+            "## count { #count }",
+            "## hello { #hello }",
+            "## Line { #line }",
+        ]);
+        assert_eq!(headings(&MarkdownOptions {
+            group_by_kind: Some(Vec::new()),
+            ..Default::default()
+        }), vec![
+            "## Functions { #kind-function }",
+            "### hello { #hello }",
+            "## Classes { #kind-class }",
+            "### Point { #point }",
+            "### Line { #line }",
+            "## Variables { #kind-variable }",
+            "### count { #count }",
+        ]);
+        assert_eq!(headings(&MarkdownOptions {
+            group_by_kind: Some(vec![Kind::Variable]),
+            ..Default::default()
+        })[0], "## Variables { #kind-variable }");
+    }
+
+    #[test]
+    fn alphabetical_sort() {
+        let json = r#"[{
+            "name": "zoom",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }, {
+            "name": "Alpha",
+            "type": "variable",
+            "rawdesc": "Second.",
+            "defines": [{"start": 20, "finish": 30, "type": "setglobal", "file": "file:///test.lua"}]
+        }, {
+            "name": "Alpha",
+            "type": "variable",
+            "rawdesc": "First.",
+            "defines": [{"start": 10, "finish": 20, "type": "setglobal", "file": "file:///test.lua"}]
+        }]"#;
+
+        let markdown = render(json);
+        assert!(markdown.find("## zoom").unwrap() < markdown.find("## Alpha").unwrap());
+
+        let markdown = render_with(json, &MarkdownOptions {
+            sort: Sort::Alphabetical,
+            ..Default::default()
+        });
+        assert!(markdown.find("## Alpha").unwrap() < markdown.find("## zoom").unwrap(), "{}", markdown);
+        assert!(markdown.find("First.").unwrap() < markdown.find("Second.").unwrap(), "{}", markdown);
+    }
+
+    #[test]
+    fn escaped_descriptions() {
+        assert_eq!(
+            escape_markdown("# Not a heading\n2 * 3 = x_y, see [1] and `a_b*c`\n```lua\nlocal t = { [1] = 2 }\n```\n> quoted <b>"),
+            "\\# Not a heading\n2 \\* 3 = x\\_y, see \\[1\\] and `a_b*c`\n```lua\nlocal t = { [1] = 2 }\n```\n\\> quoted \\<b>"
+        );
+        assert_eq!(
+            escape_markdown("See [the_docs](https://example.com/a_b) or <https://example.com/c_d>, not [x] (y) or <b>."),
+            "See [the_docs](https://example.com/a_b) or <https://example.com/c_d>, not \\[x\\] (y) or \\<b>."
+        );
+        assert_eq!(
+            escape_markdown("- one\n+ two\n1. three\n  2) four\n-1 and 3.5 and 10.x"),
+            "\\- one\n\\+ two\n1\\. three\n  2\\) four\n-1 and 3.5 and 10.x"
+        );
+
+        let json = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say *hello*.",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]
+        }]"#;
+        assert!(render(json).contains("Say \\*hello\\*."));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            raw_descriptions: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains("Say *hello*."), "{}", markdown);
+    }
+
+    #[test]
+    fn subclasses() {
+        let class = |name: &str, base: Option<&str>| {
+            let extends = base
+                .map(|base| format!(r#", "extends": [{{"start": 0, "finish": 10, "type": "doc.extends.name", "view": "{}"}}]"#, base))
+                .unwrap_or_default();
+            format!(r#"{{
+                "name": "{}",
+                "type": "type",
+                "defines": [{{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"{}}}]
+            }}"#, name, extends)
+        };
+
+        let markdown = render(&format!(
+            "[{}, {}, {}]",
+            class("Animal", None),
+            class("Dog", Some("Animal")),
+            class("Cat", Some("Animal"))
+        ));
+
+        assert!(markdown.contains("**Subclasses:**\n\n- [`Cat`](#cat)\n- [`Dog`](#dog)\n"), "{}", markdown);
+        assert_eq!(markdown.matches("**Subclasses:**").count(), 1, "{}", markdown);
+    }
+
+    #[test]
+    fn inline_signatures() {
+        assert_eq!(inline_code("a `b`"), "`` a `b` ``");
+
+        let markdown = render_with(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello(x: integer)\n  -> string"}
+            }]
+        }]"#, &MarkdownOptions {
+            signatures_inline: true,
+            ..Default::default()
+        });
+
+        assert!(markdown.contains("\n`function hello(x: integer) -> string`\n"), "{}", markdown);
+        assert!(!markdown.contains("```"), "{}", markdown);
+    }
+
+    #[test]
+    fn signature_first() {
+        let json = r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello()"}
+            }]
+        }]"#;
+
+        assert!(render(json).starts_with("## hello { #hello }\n\nSay hello.\n\n```lua\nfunction hello()\n```\n"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            signature_first: true,
+            ..Default::default()
+        });
+        assert!(markdown.starts_with("## hello { #hello }\n\n```lua\nfunction hello()\n```\n\nSay hello.\n"), "{}", markdown);
+
+        let json = r#"[{
+            "name": "Greeter",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "greet",
+                "type": "setmethod",
+                "rawdesc": "Greet someone.",
+                "start": 1,
+                "finish": 2,
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 1,
+                    "finish": 2,
+                    "type": "function",
+                    "view": "function Greeter:greet(name: string)\n  -> boolean",
+                    "args": [{"name": "name", "type": "local", "view": "string", "start": 3, "finish": 4}],
+                    "returns": [{"type": "function.return", "view": "boolean"}]
+                }
+            }]
+        }, {
+            "name": "greet",
+            "type": "variable",
+            "rawdesc": "Greet the world.",
+            "defines": [{
+                "start": 20,
+                "finish": 30,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 20,
+                    "finish": 30,
+                    "type": "function",
+                    "view": "function greet(name: string)",
+                    "args": [{"name": "name", "type": "local", "view": "string", "start": 21, "finish": 22}]
+                }
+            }]
+        }]"#;
+        let markdown = render_with(json, &MarkdownOptions {
+            signature_first: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains("```lua\nfunction greet(name: string)\n```\n\nGreet the world.\n\n### Arguments { .h-args }"), "{}", markdown);
+        assert!(markdown.contains("```lua\nfunction Greeter:greet(name: string)\n  -> boolean\n```\n\nGreet someone.\n\n#### Arguments { .h-args }"), "{}", markdown);
+        assert_eq!(markdown.matches("Greet someone.").count(), 1, "{}", markdown);
+        assert_eq!(markdown.matches("Greet the world.").count(), 1, "{}", markdown);
+    }
+
+    #[test]
+    fn definition_size() {
+        let mut file = test_file(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{
+                "start": 20004,
+                "finish": 40010,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 20004, "finish": 40010, "type": "function", "view": "function hello()"}
+            }]
+        }]"#);
+        file.source = PathBuf::from("/test.lua");
+        let symbols = SymbolIndex::from_files(std::slice::from_ref(&file));
+        let markdown = render_file(&registry(symbols).unwrap(), &file, &MarkdownOptions {
+            show_size: true,
+            ..Default::default()
+        }).unwrap();
+
+        assert!(markdown.contains("\n*Size: 20006*\n"), "{}", markdown);
+        assert!(!render(r#"[{"name": "x", "type": "variable", "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///test.lua"}]}]"#).contains("Size:"));
+    }
+
+    #[test]
+    fn usage_examples() {
+        let markdown = render(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.\n@usage\nhello(\"world\")\n@example not usage",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function hello(name: string)"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains("\n### Usage { .h-usage }\n\n```lua\nhello(\"world\")\n```\n"), "{}", markdown);
+        assert!(markdown.contains("@example not usage"), "{}", markdown);
+        assert!(!markdown.contains("@usage"), "{}", markdown);
+    }
+
+    #[test]
+    fn undocumented_placeholder() {
+        let json = r#"[{
+            "name": "Dog",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "bark",
+                "type": "setmethod",
+                "file": "file:///test.lua",
+                "start": 20000,
+                "finish": 30000,
+                "extends": {"start": 20000, "finish": 30000, "type": "function", "view": "function Dog:bark()"}
+            }]
+        }]"#;
+        let markdown = render_with(json, &MarkdownOptions {
+            undocumented_placeholder: Some("*No description.*".into()),
+            ..Default::default()
+        });
+
+        assert!(markdown.contains("## Dog { #dog }\n\n*No description.*\n"), "{}", markdown);
+        assert!(markdown.contains("### Dog:bark { #dog-bark }\n\n*No description.*\n"), "{}", markdown);
+        assert!(!render(json).contains("No description"));
+    }
+
+    #[test]
+    fn overloaded_functions() {
+        let markdown = render(r#"[{
+            "name": "clamp",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": [{
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function clamp(x: number)",
+                    "args": [{"name": "x", "type": "local", "view": "number", "start": 1, "finish": 2}]
+                }, {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function clamp(x: number, max: number)",
+                    "args": [
+                        {"name": "x", "type": "local", "view": "number", "start": 1, "finish": 2},
+                        {"name": "max", "type": "local", "view": "number", "start": 3, "finish": 4}
+                    ]
+                }]
+            }, {
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {"start": 0, "finish": 10, "type": "function", "view": "function clamp(x: number)"}
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "```lua\nfunction clamp(x: number)\n```\n\n",
+            "### Arguments { .h-args }\n\n",
+            "| Name | Type | Description |\n",
+            "| ---- | ---- | ----------- |\n",
+            "| x | `number` |  |\n",
+        )), "{}", markdown);
+        assert!(markdown.contains("### Overloads { .h-overloads }\n\n```lua\nfunction clamp(x: number, max: number)\n```\n\n#### Arguments"), "{}", markdown);
+        assert_eq!(markdown.matches("function clamp(x: number)\n").count(), 1, "{}", markdown);
+    }
+
+    #[test]
+    fn field_definition_lists() {
+        let json = r#"[{
+            "name": "Song",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "tempo",
+                "type": "doc.field",
+                "rawdesc": "The tempo in BPM.\n\nChanging it moves every note.",
+                "start": 1,
+                "finish": 2,
+                "file": "file:///test.lua",
+                "extends": {"start": 1, "finish": 2, "type": "doc.type", "view": "number"}
+            }]
+        }]"#;
+
+        let markdown = render_with(json, &MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Table),
+            ..Default::default()
+        });
+        assert!(markdown.contains("| <a id=\"song-tempo\"></a>tempo | `number` | The tempo in BPM.<br><br>Changing it moves every note. |"), "{}", markdown);
+
+        let markdown = render_with(json, &MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Table),
+            field_definition_lists: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains(concat!(
+            "### Fields { .h-fields }\n\n",
+            "<dl class=\"luacats-fields\">\n",
+            "<dt id=\"song-tempo\">\n\ntempo: `number`\n\n</dt>\n",
+            "<dd>\n\nThe tempo in BPM.\n\nChanging it moves every note.\n\n</dd>\n",
+            "</dl>\n",
+        )), "{}", markdown);
+        assert!(!markdown.contains("| Name |"), "{}", markdown);
+    }
+
+    #[test]
+    fn required_column() {
+        let json = r#"[{
+            "name": "clamp",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function clamp(x: number, max?: number)",
+                    "args": [
+                        {"name": "x", "type": "local", "view": "number", "start": 1, "finish": 2},
+                        {"name": "max", "type": "local", "view": "number?", "rawdesc": "The upper bound.", "start": 3, "finish": 4}
+                    ]
+                }
+            }]
+        }]"#;
+
+        assert!(render(json).contains("| Name | Type | Description |"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            required_column: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains("| Name | Type | Required | Description |\n| ---- | ---- | -------- | ----------- |\n"), "{}", markdown);
+        assert!(markdown.contains("| x | `number` | Yes |  |\n"), "{}", markdown);
+        assert!(markdown.contains("| max | `number?` | No | The upper bound. |\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn permalink_comments() {
+        let json = r#"[{
+            "name": "renoise.Song",
+            "type": "type",
+            "rawdesc": "A song.",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [{
+                "name": "play",
+                "type": "setmethod",
+                "start": 1,
+                "finish": 2,
+                "file": "file:///test.lua",
+                "extends": {"start": 1, "finish": 2, "type": "function", "view": "function renoise.Song:play()"}
+            }]
+        }]"#;
+
+        assert!(!render(json).contains("<!--"));
+
+        let markdown = render_with(json, &MarkdownOptions {
+            permalink_comments: true,
+            ..Default::default()
+        });
+        assert!(markdown.starts_with("<!-- luacats: renoise.Song #renoise-song -->\n## renoise.Song { #renoise-song }"), "{}", markdown);
+        assert!(markdown.contains("\n<!-- luacats: renoise.Song:play #renoise-song-play -->\n### renoise.Song:play { #renoise-song-play }"), "{}", markdown);
+    }
+
+    #[test]
+    fn overload_descriptions() {
+        let overload = |view: &str, rawdesc: &str| format!(
+            r#"{{"start": 0, "finish": 10, "type": "function", "view": "{}", "rawdesc": "{}"}}"#,
+            view, rawdesc
+        );
+        let json = format!(r#"[{{
+            "name": "clamp",
+            "type": "variable",
+            "rawdesc": "Clamp a number.",
+            "defines": [{{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": [{}, {}, {}]
+            }}]
+        }}]"#,
+            overload("function clamp(x: number)", "Clamp a number."),
+            overload("function clamp(x: number, max: number)", "Clamp a number."),
+            overload("function clamp(x: number, min: number, max: number)", "Clamp between two numbers."),
+        );
+
+        // Every overload's description is shown, even when it's the shared one
+        let default = render(&json);
+        assert_eq!(default.matches("Clamp a number.").count(), 3, "{}", default);
+        assert!(default.contains("\n\nClamp between two numbers.\n\n```lua\nfunction clamp(x: number, min: number, max: number)\n```\n"), "{}", default);
+
+        let markdown = render_with(&json, &MarkdownOptions {
+            dedupe_overload_descriptions: true,
+            ..Default::default()
+        });
+        assert_eq!(markdown.matches("Clamp a number.").count(), 1, "{}", markdown);
+        assert!(markdown.contains("\n\nClamp between two numbers.\n\n```lua\nfunction clamp(x: number, min: number, max: number)\n```\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn deep_headings_are_bold() {
+        assert_eq!(
+            clamp_headings("###### Six { #six }\n\n####### Seven { #seven }\n\n####### Arguments { .h-args }\n```\n####### code\n```"),
+            "###### Six { #six }\n\n<a id=\"seven\"></a>**Seven**\n\n**Arguments**\n```\n####### code\n```"
+        );
+
+        let options = MarkdownOptions {
+            tree_from_names: true,
+            ..Default::default()
+        };
+        let markdown = render_with(r#"[{
+            "name": "a.b.c.d.e",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setfield",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function a.b.c.d.e(x: integer)",
+                    "args": [{"name": "x", "type": "local", "view": "integer", "start": 1, "finish": 2}]
+                }
+            }]
+        }]"#, &options);
+
+        assert!(markdown.contains("###### a.b.c.d.e { #a-b-c-d-e }"), "{}", markdown);
+        assert!(markdown.contains("\n**Arguments**\n\n| Name |"), "{}", markdown);
+    }
+
+    #[test]
+    fn kind_icons() {
+        let mut icons = default_icons();
+        icons.insert("class".into(), "C".into());
+        icons.remove("field");
+        let options = MarkdownOptions {
+            icons,
+            ..Default::default()
+        };
+        let markdown = render_with(CLASS_WITH_MEMBERS, &options);
+
+        assert!(markdown.contains("## C Point { #point }"), "{}", markdown);
+        assert!(markdown.contains("### ƒ Point:length { #point-length }"), "{}", markdown);
+        assert!(render(CLASS_WITH_MEMBERS).contains("## Point { #point }"));
+    }
+
+    #[test]
+    fn glossary_lists_types() {
+        let mut file = test_file(r#"[
+            {
+                "name": "Point",
+                "type": "type",
+                "rawdesc": "A point in space. Or time.",
+                "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "Color",
+                "type": "type",
+                "defines": [{"start": 20, "finish": 30, "type": "doc.alias", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "draw",
+                "type": "variable",
+                "defines": [{
+                    "start": 40,
+                    "finish": 50,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {"start": 40, "finish": 50, "type": "function", "view": "function draw()"}
+                }]
+            }
+        ]"#);
+        file.path = PathBuf::from("geometry/test.lua");
+        let files = [file];
+        let symbols = SymbolIndex::from_files(&files);
+
+        let glossary = render_glossary(&files, &symbols, "Glossary", Path::new("glossary.md"), None);
+
+        assert_eq!(
+            glossary,
+            "# Glossary\n\n- [`Color`](geometry/test.md#color)\n- [`Point`](geometry/test.md#point) — A point in space.\n"
+        );
+    }
+
+    #[test]
+    fn module_table_counts() {
+        let definitions = |count: usize| -> Vec<Definition> {
+            (0..count)
+                .map(|index| serde_json::from_str(&format!(r#"{{"name": "d{}", "type": "variable", "defines": []}}"#, index)).unwrap())
+                .collect()
+        };
+        let file = |path: &str, count: usize, sub_files: Vec<MetaFile>| MetaFile {
+            path: PathBuf::from(path),
+            definitions: definitions(count),
+            sub_files,
+            ..Default::default()
+        };
+        let files = [
+            file("renoise.lua", 1, vec![
+                file("renoise/midi.lua", 2, Vec::new()),
+                file("renoise/song.lua", 3, Vec::new()),
+            ]),
+            file("bit.lua", 4, Vec::new()),
+        ];
+
+        assert_eq!(render_module_table(&files, "Modules", None, &MarkdownOptions::default()), concat!(
+            "# Modules\n\n",
+            "- [renoise](renoise.md) — 1 definition, 6 definitions in total\n",
+            "  - [midi](renoise/midi.md) — 2 definitions\n",
+            "  - [song](renoise/song.md) — 3 definitions\n",
+            "- [bit](bit.md) — 4 definitions\n",
+        ));
+
+        // Folded files link to their place in the ancestor's chapter
+        let options = MarkdownOptions {
+            fold_heading: true,
+            ..Default::default()
+        };
+        let markdown = render_module_table(&files, "Modules", Some(1), &options);
+        assert!(markdown.contains("  - [midi](renoise.md#module-renoise-midi) — 2 definitions\n"), "{}", markdown);
+        let markdown = render_module_table(&files, "Modules", Some(1), &MarkdownOptions::default());
+        assert!(markdown.contains("  - [song](renoise.md) — 3 definitions\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn summary_and_detailed_signatures() {
+        let json = r#"[{
+            "name": "clamp",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function clamp(value: number, range: table<string, number>)\n  -> number"
+                }
+            }]
+        }]"#;
+        let options = MarkdownOptions {
+            signature_summary: true,
+            detailed_signatures: true,
+            ..Default::default()
+        };
+        let markdown = render_with(json, &options);
+
+        assert!(markdown.contains("## clamp { #clamp }\n\n`function clamp(value: number, range: table<string, number>) -> number`\n"), "{}", markdown);
+        assert!(markdown.contains("```lua\nfunction clamp(\n  value: number,\n  range: table<string, number>,\n)\n  -> number\n```"), "{}", markdown);
+
+        let markdown = render(json);
+        assert!(!markdown.contains("`function clamp"), "{}", markdown);
+        assert!(markdown.contains("```lua\nfunction clamp(value: number, range: table<string, number>)\n  -> number\n```"), "{}", markdown);
+    }
+
+    #[test]
+    fn numbered_returns() {
+        let function = |returns: &str| {
+            format!(r#"[{{
+                "name": "divide",
+                "type": "variable",
+                "defines": [{{
+                    "start": 0,
+                    "finish": 10,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {{"start": 0, "finish": 10, "type": "function", "view": "function divide()", "returns": [{}]}}
+                }}]
+            }}]"#, returns)
+        };
+
+        let markdown = render(&function(r#"
+            {"name": "quotient", "type": "function.return", "view": "integer", "rawdesc": "The result."},
+            {"name": "remainder", "type": "function.return", "view": "integer"}
+        "#));
+        assert!(markdown.contains("| # | Name | Type | Description |"), "{}", markdown);
+        assert!(markdown.contains("| 1 | quotient | `integer` | The result. |\n| 2 | remainder | `integer` |  |"), "{}", markdown);
+
+        let markdown = render(&function(r#"{"type": "function.return", "view": "integer"}"#));
+        assert!(markdown.contains("### Returns { .h-returns }\n\n| Name | Type | Description |"), "{}", markdown);
+
+        let markdown = render(&function(""));
+        assert!(!markdown.contains("Returns"), "{}", markdown);
+    }
+
+    #[test]
+    fn sort_by_order() {
+        let options = MarkdownOptions {
+            sort_by_order: true,
+            ..Default::default()
+        };
+        let markdown = render_with(r#"[
+            {"name": "zeta", "type": "variable", "defines": [{"start": 0, "finish": 1, "type": "setglobal", "file": "file:///test.lua"}]},
+            {"name": "gamma", "type": "variable", "rawdesc": "Third.\n@order 2", "defines": [{"start": 10, "finish": 11, "type": "setglobal", "file": "file:///test.lua"}]},
+            {"name": "alpha", "type": "variable", "defines": [{"start": 20, "finish": 21, "type": "setglobal", "file": "file:///test.lua"}]},
+            {"name": "beta", "type": "variable", "rawdesc": "First.\n@order 1", "defines": [{"start": 30, "finish": 31, "type": "setglobal", "file": "file:///test.lua"}]}
+        ]"#, &options);
+
+        let headings: Vec<&str> = markdown.lines().filter(|line| line.starts_with("## ")).collect();
+
+        assert_eq!(headings, vec!["## beta { #beta }", "## gamma { #gamma }", "## alpha { #alpha }", "## zeta { #zeta }"]);
+        assert!(!markdown.contains("@order"), "{}", markdown);
+    }
+
+    const CLASS_WITH_MEMBERS: &str = r#"[{
+        "name": "Point",
+        "type": "type",
+        "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+        "fields": [
+            {
+                "name": "x",
+                "type": "doc.field",
+                "rawdesc": "The horizontal position.",
+                "start": 10,
+                "finish": 20,
+                "file": "file:///test.lua",
+                "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "number"}
+            },
+            {
+                "name": "length",
+                "type": "setmethod",
+                "rawdesc": "The distance from the origin.",
+                "start": 30,
+                "finish": 40,
+                "file": "file:///test.lua",
+                "extends": {"start": 30, "finish": 40, "type": "function", "view": "(method) Point:length()\n  -> number"}
+            }
+        ]
+    }]"#;
+
+    #[test]
+    fn table_class_members() {
+        let json = r#"[{
+            "name": "Point",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [
+                {"name": "y", "type": "doc.field", "rawdesc": "The vertical position.", "start": 20, "finish": 30, "file": "file:///test.lua",
+                 "extends": {"start": 20, "finish": 30, "type": "doc.type", "view": "number"}},
+                {"name": "x", "type": "doc.field", "start": 10, "finish": 20, "file": "file:///test.lua",
+                 "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "number"}},
+                {"name": "length", "type": "setmethod", "start": 30, "finish": 40, "file": "file:///test.lua",
+                 "extends": {"start": 30, "finish": 40, "type": "function", "view": "(method) Point:length()"}},
+                {"name": "z", "type": "doc.field", "start": 50, "finish": 60, "file": "file:///a.lua",
+                 "extends": {"start": 50, "finish": 60, "type": "doc.type", "view": "number"}}
+            ]
+        }]"#;
+
+        let markdown = render(json);
+        let x = markdown.find("### Point.x { #point-x }").expect(&markdown);
+        let y = markdown.find("### Point.y { #point-y }").expect(&markdown);
+        let z = markdown.find("### Point.z { #point-z }").expect(&markdown);
+        assert!(z < x && x < y, "{}", markdown);
+        assert!(!markdown.contains("### Fields"), "{}", markdown);
+
+        let markdown = render_with(json, &MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Table),
+            ..Default::default()
+        });
+        assert!(markdown.contains(concat!(
+            "### Fields { .h-fields }\n\n",
+            "| Name | Type | Description |\n",
+            "| ---- | ---- | ----------- |\n",
+            "| <a id=\"point-z\"></a>z | `number` |  |\n",
+            "| <a id=\"point-x\"></a>x | `number` |  |\n",
+            "| <a id=\"point-y\"></a>y | `number` | The vertical position. |\n",
+        )), "{}", markdown);
+        assert!(markdown.contains("### Point:length { #point-length }"), "{}", markdown);
+        assert!(!markdown.contains("### Point.x"), "{}", markdown);
+    }
+
+    #[test]
+    fn long_unions_as_lists() {
+        let json = r#"[{
+            "name": "Shape",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}],
+            "fields": [
+                {"name": "kind", "type": "doc.field", "start": 10, "finish": 20, "file": "file:///test.lua",
+                 "extends": {"start": 10, "finish": 20, "type": "doc.type", "view": "\"circle\"|\"square\"|\"triangle\"|Shape"}},
+                {"name": "size", "type": "doc.field", "start": 20, "finish": 30, "file": "file:///test.lua",
+                 "extends": {"start": 20, "finish": 30, "type": "doc.type", "view": "number|nil"}}
+            ]
+        }]"#;
+        let mut options = MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Table),
+            ..Default::default()
+        };
+        assert!(render_with(json, &options).contains("kind | `\"circle\"\\|\"square\"\\|\"triangle\"\\|`[`Shape`](#shape) |"));
+
+        options.union_list_threshold = Some(3);
+        let markdown = render_with(json, &options);
+        assert!(markdown.contains("kind | <ul><li>`\"circle\"`</li><li>`\"square\"`</li><li>`\"triangle\"`</li><li>[`Shape`](#shape)</li></ul> |"), "{}", markdown);
+        assert!(markdown.contains("size | `number\\|nil` |"), "{}", markdown);
+    }
+
+    #[test]
+    fn linked_signatures() {
+        let json = r#"[
+            {
+                "name": "Song",
+                "type": "type",
+                "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///test.lua"}]
+            },
+            {
+                "name": "load",
+                "type": "variable",
+                "defines": [{
+                    "start": 20,
+                    "finish": 30,
+                    "type": "setglobal",
+                    "file": "file:///test.lua",
+                    "extends": {"start": 20, "finish": 30, "type": "function", "view": "function load(path: string)\n  -> Song"}
+                }]
+            }
+        ]"#;
+        let options = MarkdownOptions {
+            link_signatures: true,
+            ..Default::default()
+        };
+        let markdown = render_with(json, &options);
+
+        assert!(markdown.contains("<pre><code class=\"luacats-signature\">function load(path: string)\n  -&gt; <a href=\"#song\">Song</a></code></pre>"), "{}", markdown);
+        assert!(!markdown.contains("```lua\nfunction load"), "{}", markdown);
+    }
+
+    #[test]
+    fn union_table_cells() {
+        let markdown = render(r#"[{
+            "name": "find",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "function",
+                    "view": "function find(name: string|nil)\n  -> integer|nil",
+                    "args": [{"name": "name", "type": "local", "view": "string|nil", "start": 1, "finish": 2}],
+                    "returns": [{"type": "function.return", "view": "integer|nil"}]
+                }
+            }]
+        }]"#);
+
+        assert!(markdown.contains("| name | `string\\|nil` |  |\n"), "{}", markdown);
+        assert!(markdown.contains("|  | `integer\\|nil` |  |\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn table_constants() {
+        let markdown = render(r#"[{
+            "name": "defaults",
+            "type": "variable",
+            "defines": [{
+                "start": 0,
+                "finish": 10,
+                "type": "setglobal",
+                "file": "file:///test.lua",
+                "extends": {
+                    "start": 0,
+                    "finish": 10,
+                    "type": "table",
+                    "view": "{\n    debug: boolean = false,\n    name: string = \"a, b\",\n    sizes: table<string, integer>,\n}"
+                }
+            }]
+        }]"#);
+
+        assert!(markdown.contains(concat!(
+            "### Keys { .h-keys }\n\n",
+            "| Key | Type | Value |\n",
+            "| --- | ---- | ----- |\n",
+            "| debug | `boolean` | `false` |\n",
+            "| name | `string` | `\"a, b\"` |\n",
+            "| sizes | `table<string, integer>` |  |\n",
+        )), "{}", markdown);
+        assert!(!markdown.contains("```lua"), "{}", markdown);
+    }
+
+    #[test]
+    fn combined_class_members() {
+        let options = MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Combined),
+            ..Default::default()
+        };
+        let markdown = render_with(CLASS_WITH_MEMBERS, &options);
+
+        assert!(markdown.contains("### Members"), "{}", markdown);
+        assert!(markdown.contains("| x | field | `number` | The horizontal position. |"), "{}", markdown);
+        assert!(markdown.contains("| [length](#point-length) | method | `function` | The distance from the origin. |"), "{}", markdown);
+        assert!(markdown.contains("### Point:length { #point-length }"), "{}", markdown);
+        assert!(!markdown.contains("### Point.x"), "{}", markdown);
+    }
+
+    #[test]
+    fn split_class_members() {
+        let options = MarkdownOptions {
+            class_members_layout: Some(ClassMembersLayout::Split),
+            ..Default::default()
+        };
+        let markdown = render_with(CLASS_WITH_MEMBERS, &options);
+
+        assert!(markdown.contains("### Fields"), "{}", markdown);
+        assert!(markdown.contains("| x | `number` | The horizontal position. |"), "{}", markdown);
+        assert!(markdown.contains("### Methods"), "{}", markdown);
+        assert!(markdown.contains("#### Point:length { #point-length }"), "{}", markdown);
+        assert!(markdown.find("### Fields") < markdown.find("### Methods"));
+    }
+
+    const DUPLICATE_DEFINITIONS: &str = r#"[
+        {"name": "dup", "type": "variable", "defines": [{"start": 0, "finish": 1, "type": "setglobal", "file": "file:///test.lua"}]},
+        {"name": "dup", "type": "variable", "defines": [{"start": 10, "finish": 11, "type": "setglobal", "file": "file:///test.lua"}]},
+        {"name": "dup", "type": "variable", "defines": [{"start": 20, "finish": 21, "type": "setglobal", "file": "file:///test.lua"}]}
+    ]"#;
+
+    #[test]
+    fn duplicate_anchors() {
+        let markdown = render(DUPLICATE_DEFINITIONS);
+
+        let headings: Vec<&str> = markdown.lines().filter(|line| line.starts_with("## ")).collect();
+        assert_eq!(headings, vec!["## dup { #dup }", "## dup { #dup-1 }", "## dup { #dup-2 }"]);
+
+        // The table of contents and permalinks use the same anchors
+        let markdown = render_with(DUPLICATE_DEFINITIONS, &MarkdownOptions {
+            emit_toc: true,
+            permalink_comments: true,
+            ..Default::default()
+        });
+        assert!(markdown.contains("- [dup](#dup)\n- [dup](#dup-1)\n- [dup](#dup-2)\n"), "{}", markdown);
+        assert!(markdown.contains("<!-- luacats: dup #dup-2 -->"), "{}", markdown);
+
+        let options = MarkdownOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let result = render_file(&registry(SymbolIndex::new()).unwrap(), &test_file(DUPLICATE_DEFINITIONS), &options);
+        assert!(result.unwrap_err().to_string().contains("dup"));
+    }
+}
@@ -1,63 +1,526 @@
-use handlebars::{no_escape, Handlebars};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use handlebars::Handlebars;
 use mdbook::{book::{Book, Chapter, SectionNumber}, preprocess::{Preprocessor, PreprocessorContext}, BookItem};
 use mdbook::errors::Error as MdBookError;
-use rust_embed::Embed;
-use std::{env, path::PathBuf};
-use toml::value::Table;
+use itertools::Itertools;
+use std::{collections::{HashMap, HashSet}, env, fs, path::{Path, PathBuf}, time::Duration};
+use toml::{value::Table, Value};
 use log::*;
 
-use crate::{luals::generate_docs, workspace::{MetaFile, Workspace}};
-
-#[derive(Embed)]
-#[folder = "templates"]
-#[include = "*.hbs"]
-struct Assets;
+use crate::{links::SymbolIndex, lua_cats::{Definition, Kind}, luals::{generate_docs_cached, generate_docs_with_timeout, read_docs, DEFAULT_LUALS_TIMEOUT}, markdown::{self, ClassMembersLayout, LastUpdated, MarkdownOptions, ReturnsLayout, Sort, TagStyle}, workspace::{MetaFile, Workspace}};
 
 /// Configuration for the preprocessor.
 #[derive(Debug, Default)]
 pub struct Config {
-    definitions_path: Option<PathBuf>,
+    /// Definition roots in priority order, later roots take precedence.
+    definitions_paths: Vec<PathBuf>,
+    /// The part titles configured for each definitions path.
+    root_titles: Vec<Option<String>>,
+    /// Give each definitions path its own part, instead of merging them.
+    part_per_root: bool,
     part_title: Option<String>,
+    /// The title of a glossary chapter listing every documented type, which
+    /// is only added when set.
+    glossary_title: Option<String>,
+    /// The title of a chapter listing every module with its number of
+    /// definitions, which is only added when set.
+    modules_title: Option<String>,
+    /// How many levels of chapters to nest, with deeper files folded into
+    /// their nearest ancestor's chapter.
     nav_depth: Option<u8>,
+    /// Document definitions from outside the definitions paths, like the
+    /// LuaLS standard library, under their own part.
+    include_stdlib: bool,
+    /// Document symbols annotated as `@private`, `@package` or `@hidden`,
+    /// when `show-private = true`. `include-private` is still read as an
+    /// older spelling.
+    show_private: bool,
+    /// Leave out symbols annotated as `@protected` along with private ones,
+    /// when `show-protected = false`.
+    hide_protected: bool,
+    /// Merge chapters with a single sub chapter into one chapter.
+    collapse_single_child: bool,
+    /// Only document the symbols whose name starts with this.
+    name_prefix: Option<String>,
+    /// Only document the files matching these globs, relative to their root.
+    include: Option<GlobSet>,
+    /// Don't document the files matching these globs, relative to their root.
+    exclude: Option<GlobSet>,
+    /// How long LuaLS may take to generate docs before it's killed.
+    lua_ls_timeout: Option<Duration>,
+    /// Run LuaLS on every build, when `cache-docs = false`. Otherwise the
+    /// doc.json LuaLS generated last time is reused when the definitions
+    /// haven't changed. A rebuild can be forced by running mdbook with
+    /// `MDBOOK_PREPROCESSOR__LUACATS__CACHE_DOCS=false`.
+    skip_doc_cache: bool,
+    /// Where cached docs are kept, by default in the book's build directory.
+    doc_cache_dir: Option<PathBuf>,
+    /// A doc.json generated ahead of time, read instead of running LuaLS.
+    /// Relative paths are relative to the book root.
+    doc_json_path: Option<PathBuf>,
+    /// Where to write a `SUMMARY.md` fragment listing the generated
+    /// chapters, for including them in the book's summary by hand. The
+    /// generated chapters aren't added to the book then, only used to fill
+    /// in the chapters the summary lists at the same paths.
+    summary_fragment_path: Option<PathBuf>,
+    markdown: MarkdownOptions,
 }
 
-impl<'a> From<Option<&'a Table>> for Config {
-    fn from(table: Option<&'a Table>) -> Config {
+impl Config {
+    /// The title of the API reference part, with any `{title}` placeholder
+    /// replaced by the book's title.
+    fn part_title(&self, book_title: Option<&str>) -> String {
+        match &self.part_title {
+            Some(part_title) => part_title.replace("{title}", book_title.unwrap_or_default()).trim().to_owned(),
+            None => "API Reference".into(),
+        }
+    }
+
+    /// Drop the definitions whose name doesn't start with `name-prefix`.
+    fn retain_docs(&self, docs: &mut Vec<Definition>) {
+        if let Some(prefix) = &self.name_prefix {
+            docs.retain(|definition| definition.name.starts_with(prefix.as_str()));
+        }
+    }
+
+    /// Remove the symbols that aren't meant for readers, unless private
+    /// symbols are included.
+    fn remove_hidden(&self, workspace: &mut Workspace) {
+        if self.show_private {
+            return;
+        }
+        workspace.remove_hidden();
+        if self.hide_protected {
+            workspace.remove_protected();
+        }
+    }
+
+    /// Whether a file, by its path relative to its root, passes the
+    /// `include` and `exclude` globs.
+    fn is_file_included(&self, path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|include| include.is_match(path))
+            && !self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(path))
+    }
+}
+
+/// Parse a glob or array of globs.
+fn glob_set(table: &Table, key: &str) -> anyhow::Result<Option<GlobSet>> {
+    let globs: Vec<&str> = match table.get(key) {
+        Some(Value::String(glob)) => vec![glob],
+        Some(Value::Array(globs)) => globs.iter().filter_map(|v| v.as_str()).collect(),
+        Some(v) => return Err(anyhow::anyhow!("{} must be a glob or an array of globs, not {}", key, v)),
+        None => return Ok(None),
+    };
+
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| anyhow::anyhow!("Invalid {} glob: {}", key, err))?);
+    }
+
+    Ok(Some(builder.build()?))
+}
+
+impl<'a> TryFrom<Option<&'a Table>> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(table: Option<&'a Table>) -> anyhow::Result<Config> {
         let mut config = Config::default();
 
         if let Some(table) = table {
-            config.definitions_path = table
-                .get("definitions-path")
-                .and_then(|v| v.as_str())
-                .and_then(|v| Some(v.to_owned().into()));
+            (config.definitions_paths, config.root_titles) = match table.get("definitions-path") {
+                Some(Value::String(path)) => (vec![path.into()], vec![None]),
+                Some(Value::Array(paths)) => paths
+                    .iter()
+                    .filter_map(|v| match v {
+                        Value::String(path) => Some((PathBuf::from(path), None)),
+                        Value::Table(root) => Some((
+                            PathBuf::from(root.get("path")?.as_str()?),
+                            root.get("title").and_then(|v| v.as_str()).map(str::to_owned),
+                        )),
+                        _ => None,
+                    })
+                    .unzip(),
+                _ => (Vec::new(), Vec::new()),
+            };
+
+            config.part_per_root = table
+                .get("part-per-root")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
 
             config.part_title = table
                 .get("part-title")
                 .and_then(|v| v.as_str())
-                .and_then(|v| Some(v.to_owned()));
+                .map(|v| v.to_owned());
+
+            config.modules_title = table
+                .get("modules-title")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.glossary_title = table
+                .get("glossary-title")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.nav_depth = match table.get("nav-depth") {
+                Some(Value::Integer(depth)) if u8::try_from(*depth).is_ok() => Some(*depth as u8),
+                Some(v) => return Err(anyhow::anyhow!("nav-depth must be a number from 0 to 255, not {}", v)),
+                None => None,
+            };
+
+            config.lua_ls_timeout = match table.get("lua-ls-timeout") {
+                Some(Value::Integer(secs)) if *secs > 0 => Some(Duration::from_secs(*secs as u64)),
+                Some(v) => return Err(anyhow::anyhow!("lua-ls-timeout must be a number of seconds, not {}", v)),
+                None => None,
+            };
+
+            config.skip_doc_cache = match table.get("cache-docs") {
+                Some(Value::Boolean(cache_docs)) => !cache_docs,
+                Some(v) => return Err(anyhow::anyhow!("cache-docs must be true or false, not {}", v)),
+                None => false,
+            };
+
+            config.doc_cache_dir = table
+                .get("doc-cache-dir")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from);
+
+            config.doc_json_path = table
+                .get("doc-json-path")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from);
+
+            config.summary_fragment_path = table
+                .get("summary-fragment-path")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from);
+
+            config.include_stdlib = table
+                .get("include-stdlib")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.collapse_single_child = table
+                .get("collapse-single-child")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.name_prefix = table
+                .get("name-prefix")
+                .and_then(|v| v.as_str())
+                .map(str::to_owned);
+
+            config.include = glob_set(table, "include")?;
+            config.exclude = glob_set(table, "exclude")?;
+
+            config.hide_protected = !table
+                .get("show-protected")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+
+            config.show_private = table
+                .get("show-private")
+                .or_else(|| table.get("include-private"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.compact = table
+                .get("compact")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.source_base_url = table
+                .get("source-base-url")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.markdown.source_url_template = table
+                .get("source-url-template")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.markdown.tree_from_names = table
+                .get("tree-from-names")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.sort_by_order = table
+                .get("sort-by-order")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.class_members_layout = table
+                .get("class-members-layout")
+                .and_then(|v| v.as_str())
+                .and_then(|v| match v {
+                    "combined" => Some(ClassMembersLayout::Combined),
+                    "split" => Some(ClassMembersLayout::Split),
+                    "table" => Some(ClassMembersLayout::Table),
+                    _ => {
+                        warn!("Ignoring unknown class-members-layout {:?}", v);
+                        None
+                    }
+                });
+
+            config.markdown.returns_layout = table
+                .get("returns-layout")
+                .and_then(|v| v.as_str())
+                .and_then(|v| match v {
+                    "table" => Some(ReturnsLayout::Table),
+                    "inline" => Some(ReturnsLayout::Inline),
+                    _ => {
+                        warn!("Ignoring unknown returns-layout {:?}", v);
+                        None
+                    }
+                })
+                .unwrap_or_default();
+
+            config.markdown.strict = table
+                .get("strict")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.github_flavored = table
+                .get("github-flavored")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.assets = table
+                .get("assets")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.member_counts = table
+                .get("member-counts")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.params_in_heading = match table.get("params-in-heading") {
+                Some(Value::Integer(count)) if *count >= 0 => Some(*count as usize),
+                Some(v) => return Err(anyhow::anyhow!("params-in-heading must be a number of parameters, not {}", v)),
+                None => None,
+            };
+
+            config.markdown.fold_heading = table
+                .get("fold-heading")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.icons = match table.get("icons") {
+                Some(Value::Boolean(true)) => markdown::default_icons(),
+                Some(Value::Table(icons)) => {
+                    let mut defaults = markdown::default_icons();
+                    for (kind, icon) in icons.iter() {
+                        if !defaults.contains_key(kind) {
+                            warn!("Ignoring icon for unknown kind {:?}", kind);
+                            continue;
+                        }
+                        match icon.as_str() {
+                            Some("") => defaults.remove(kind),
+                            Some(icon) => defaults.insert(kind.clone(), icon.to_owned()),
+                            None => continue,
+                        };
+                    }
+                    defaults
+                }
+                _ => Default::default(),
+            };
+
+            config.markdown.signature_summary = table
+                .get("signature-summary")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.detailed_signatures = table
+                .get("detailed-signatures")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.union_list_threshold = match table.get("union-list-threshold") {
+                Some(Value::Integer(count)) if *count >= 0 => Some(*count as usize),
+                Some(v) => return Err(anyhow::anyhow!("union-list-threshold must be a number of types, not {}", v)),
+                None => None,
+            };
+
+            config.markdown.link_signatures = table
+                .get("link-signatures")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.signatures_inline = table
+                .get("signatures-inline")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.emit_index = table
+                .get("emit-index")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.field_definition_lists = table
+                .get("field-definition-lists")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.required_column = table
+                .get("required-column")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.permalink_comments = table
+                .get("permalink-comments")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.dedupe_overload_descriptions = table
+                .get("dedupe-overload-descriptions")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.signature_first = table
+                .get("signature-first")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.show_size = table
+                .get("show-size")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.undocumented_placeholder = table
+                .get("undocumented-placeholder")
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+                .map(String::from);
+
+            config.markdown.raw_descriptions = !table
+                .get("escape-descriptions")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+
+            config.markdown.sort = table
+                .get("sort")
+                .and_then(|v| v.as_str())
+                .and_then(|v| match v {
+                    "source-order" => Some(Sort::SourceOrder),
+                    "alphabetical" => Some(Sort::Alphabetical),
+                    _ => {
+                        warn!("Ignoring unknown sort {:?}", v);
+                        None
+                    }
+                })
+                .unwrap_or_default();
+
+            config.markdown.group_by_kind = match table.get("group-by-kind") {
+                Some(Value::Boolean(true)) => Some(Vec::new()),
+                Some(Value::Array(kinds)) => Some(
+                    kinds
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .filter_map(|kind| {
+                            let parsed = Kind::from_name(kind);
+                            if parsed.is_none() {
+                                warn!("Ignoring unknown kind {:?} in group-by-kind", kind);
+                            }
+                            parsed
+                        })
+                        .collect(),
+                ),
+                _ => None,
+            };
+
+            config.markdown.locale = table
+                .get("locale")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.markdown.param_links = table
+                .get("param-links")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.emit_toc = table
+                .get("emit-toc")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.structured_signatures = table
+                .get("structured-signatures")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.deprecated_last = table
+                .get("deprecated-last")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+
+            config.markdown.code_fence_language = table
+                .get("code-fence-language")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned());
+
+            config.markdown.tag_style = table
+                .get("tag-style")
+                .and_then(|v| v.as_str())
+                .and_then(|v| match v {
+                    "badge" => Some(TagStyle::Badge),
+                    "code" => Some(TagStyle::Code),
+                    "bold" => Some(TagStyle::Bold),
+                    _ => {
+                        warn!("Ignoring unknown tag-style {:?}", v);
+                        None
+                    }
+                })
+                .unwrap_or_default();
 
-            config.nav_depth = table
-                .get("nav-depth")
-                .and_then(|v| v.as_integer())
-                .and_then(|v| Some(v.try_into().expect("nav-depth overflow")));
+            config.markdown.last_updated = table
+                .get("last-updated")
+                .and_then(|v| v.as_str())
+                .and_then(|v| match v {
+                    "git" => Some(LastUpdated::Git),
+                    "mtime" => Some(LastUpdated::Mtime),
+                    _ => {
+                        warn!("Ignoring unknown last-updated source {:?}", v);
+                        None
+                    }
+                });
+
+            config.markdown.heading_level = match table.get("heading-level") {
+                Some(Value::Integer(level @ 1..=6)) => Some(*level as usize),
+                Some(level) => return Err(anyhow::anyhow!("heading-level must be a number from 1 to 6, not {}", level)),
+                None => None,
+            };
         }
 
-        config
+        Ok(config)
     }    
 }
 
 /// A mdbook preprocessor that generates LuaCATS API docs.
-pub struct LuaCats;
+pub struct LuaCats {
+    /// Where to write the loaded workspace as JSON, if anywhere.
+    export_workspace: Option<PathBuf>,
+}
 
 impl LuaCats {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Also write the loaded workspace to a JSON file when the book is built.
+    pub fn export_workspace<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.export_workspace = Some(path.into());
+        self
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for LuaCats {
     fn default() -> Self {
-        Self {}
+        Self {
+            export_workspace: None,
+        }
     }
 }
 
@@ -67,41 +530,137 @@ impl Preprocessor for LuaCats {
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, MdBookError> {
-        let config: Config = ctx.config.get_preprocessor(self.name()).into();
+        // Books usually configure the preprocessor as `[preprocessor.luacats]`
+        let table = ctx
+            .config
+            .get_preprocessor(self.name())
+            .or_else(|| ctx.config.get_preprocessor("luacats"));
+        let config = Config::try_from(table)?;
+        let part_title = config.part_title(ctx.config.book.title.as_deref());
 
         debug!("Using mdbook root: {:?}", ctx.root);
-        debug!("Using definitions path: {:?}", config.definitions_path);
+        debug!("Using definitions paths: {:?}", config.definitions_paths);
 
         let mut root = ctx.root.clone();
         if root.is_relative() {
             root = env::current_dir()?.join(ctx.root.clone())
         }
-        let mut root_path = config.definitions_path
-            .unwrap_or_else(|| PathBuf::from("library"));
-        if root_path.is_relative() {
-            root_path = root.join(root_path);
+        let mut root_paths = config.definitions_paths.clone();
+        if root_paths.is_empty() {
+            root_paths.push(PathBuf::from("library"));
         }
-        debug!("Using root path: {:?}", root_path);
+        for root_path in root_paths.iter_mut() {
+            if root_path.is_relative() {
+                *root_path = root.join(&root_path);
+            }
+        }
+        debug!("Using root paths: {:?}", root_paths);
 
-        let docs = generate_docs(&root_path)?;
+        let mut docs = match &config.doc_json_path {
+            Some(doc_json_path) => {
+                let doc_json_path = root.join(doc_json_path);
+                debug!("Reading docs from {:?}", doc_json_path);
+                read_docs(&doc_json_path)?
+            }
+            None => {
+                let timeout = config.lua_ls_timeout.unwrap_or(DEFAULT_LUALS_TIMEOUT);
+                let cache_dir = match &config.doc_cache_dir {
+                    Some(dir) => root.join(dir),
+                    None => root.join(&ctx.config.build.build_dir).join("luacats-cache"),
+                };
+                let mut docs = Vec::new();
+                for root_path in root_paths.iter() {
+                    if config.skip_doc_cache {
+                        docs.extend(generate_docs_with_timeout(root_path, timeout)?);
+                    } else {
+                        docs.extend(generate_docs_cached(root_path, timeout, &cache_dir)?);
+                    }
+                }
+                // Each run also reports the definitions shared between the
+                // roots, such as the stdlib
+                dedupe_docs(&mut docs);
+                docs
+            }
+        };
         debug!("Generated {} definitions", docs.len());
+        config.retain_docs(&mut docs);
 
-        let mut workspace = Workspace::new(root_path);
-        workspace.load(docs)?;
+        let stdlib = if config.include_stdlib {
+            let mut stdlib = Workspace::external(&docs, &root_paths, "stdlib")?;
+            config.remove_hidden(&mut stdlib);
+            debug!("Loaded {} stdlib files", stdlib.files.len());
+            stdlib
+        } else {
+            Workspace::default()
+        };
+
+        let (workspace, parts) = load_parts(&config, part_title, root_paths, docs)?;
         debug!("Loaded {} root files", workspace.files.len());
 
-        let part_title = config.part_title.unwrap_or("API Reference".into());
-        book.push_item(BookItem::PartTitle(part_title));
+        if let Some(path) = &self.export_workspace {
+            workspace.export_json(path)?;
+            debug!("Exported workspace to {:?}", path);
+        }
 
-        let mut hbs = Handlebars::new();
-        hbs.set_strict_mode(true);
-        hbs.register_embed_templates_with_extension::<Assets>(".hbs")?;
-        hbs.register_escape_fn(no_escape);
-        
-        for (index, file) in workspace.files.iter().enumerate() {
-            let chapter = build_chapter(&hbs, file, index, None)?;
-            book.push_item(BookItem::Chapter(chapter));
-         }
+        let nav_depth = config.nav_depth.map(usize::from);
+        let mut symbols = SymbolIndex::with_nav_depth(&workspace.files, nav_depth);
+        for file in stdlib.files.iter() {
+            symbols.insert_nested(file, nav_depth);
+        }
+        let glossary = config.glossary_title.map(|title| {
+            let path = PathBuf::from("glossary.md");
+            let content = markdown::render_glossary(&workspace.files, &symbols, &title, &path, config.markdown.locale.as_deref());
+            Chapter {
+                name: title,
+                content,
+                number: None,
+                sub_items: Vec::new(),
+                path: Some(path),
+                source_path: None,
+                parent_names: Vec::new(),
+            }
+        });
+
+        let modules = config.modules_title.map(|title| {
+            let path = PathBuf::from("modules.md");
+            let files = workspace.files.iter().chain(stdlib.files.iter());
+            let content = markdown::render_module_table(files, &title, nav_depth, &config.markdown);
+            Chapter {
+                name: title,
+                content,
+                number: None,
+                sub_items: Vec::new(),
+                path: Some(path),
+                source_path: None,
+                parent_names: Vec::new(),
+            }
+        });
+
+        let hbs = markdown::registry(symbols)?;
+
+        let mut generated = Book::new();
+        push_parts(&mut generated, &hbs, &config.markdown, nav_depth, parts, &workspace.files)?;
+        // The glossary and module table follow on from the last part's chapters
+        for mut chapter in glossary.into_iter().chain(modules) {
+            chapter.number = Some(SectionNumber(vec![next_number(&generated.sections)]));
+            generated.push_item(BookItem::Chapter(chapter));
+        }
+
+        if !stdlib.files.is_empty() {
+            push_part(&mut generated, &hbs, &config.markdown, nav_depth, "Standard Library".into(), &stdlib.files)?;
+        }
+
+        match &config.summary_fragment_path {
+            Some(path) => {
+                let path = root.join(path);
+                fs::write(&path, summary_fragment(&generated.sections))?;
+                debug!("Wrote summary fragment to {:?}", path);
+                fill_chapters(&mut book, generated);
+            }
+            None => book.sections.extend(generated.sections),
+        }
+
+        check_unique_paths(&book)?;
 
         Ok(book)
     }
@@ -111,9 +670,211 @@ impl Preprocessor for LuaCats {
     }
 }
 
-fn build_chapter(hbs: &Handlebars, file: &MetaFile, index: usize, parent: Option<&Chapter>) -> anyhow::Result<Chapter> {
-    let name = file.file_stem(); 
-    let content = hbs.render("meta_file", &file)?;
+/// Drop the definitions with the same name and define locations as an
+/// earlier one.
+fn dedupe_docs(docs: &mut Vec<Definition>) {
+    let mut seen: HashSet<(String, Vec<(String, u64)>)> = HashSet::new();
+    docs.retain(|definition| {
+        let locations = definition.defines.iter().map(|define| (define.file.clone(), define.start)).sorted().collect();
+        seen.insert((definition.name.clone(), locations))
+    });
+}
+
+/// Load the workspace, along with the title and number of top level files of
+/// each part, in order. There's a part for each root when `part-per-root` is
+/// set, titled after the root's folder unless it has a title of its own, with
+/// the part's chapters below a folder named after the root. Otherwise the
+/// roots are merged into a single part.
+fn load_parts(config: &Config, part_title: String, root_paths: Vec<PathBuf>, docs: Vec<Definition>) -> anyhow::Result<(Workspace, Vec<(String, usize)>)> {
+    let mut workspace = Workspace::with_roots(root_paths);
+    workspace.load(docs)?;
+    check_loaded(&workspace, config.markdown.strict)?;
+    workspace.retain_files(|path| config.is_file_included(path));
+    config.remove_hidden(&mut workspace);
+    if config.collapse_single_child {
+        workspace.collapse_single_child();
+    }
+
+    if !config.part_per_root || workspace.roots.len() < 2 {
+        let parts = vec![(part_title, workspace.files.len())];
+        return Ok((workspace, parts));
+    }
+
+    // Each file goes in the part of the root it was loaded from
+    let mut parts = Vec::new();
+    for (index, mut files) in workspace.split_by_root().into_iter().enumerate() {
+        let name = workspace.roots[index].file_name().map(|name| name.to_string_lossy().into_owned());
+        let title = config.root_titles.get(index).cloned().flatten()
+            .or_else(|| name.clone())
+            .unwrap_or_else(|| part_title.clone());
+        let folder = name.unwrap_or_else(|| index.to_string());
+        for file in files.iter_mut() {
+            file.prefix(Path::new(&folder));
+        }
+        parts.push((title, files.len()));
+        workspace.files.extend(files);
+    }
+
+    Ok((workspace, parts))
+}
+
+/// Render a single file (and its sub files) to a top level chapter.
+///
+/// Types are only cross-linked within the file, since the rest of the
+/// workspace isn't known.
+pub fn render_chapter(file: &MetaFile, options: &MarkdownOptions) -> anyhow::Result<Chapter> {
+    let hbs = markdown::registry(SymbolIndex::from_files(std::slice::from_ref(file)))?;
+
+    build_chapter(&hbs, options, None, file, 0, None)
+}
+
+/// Warn (or fail, in strict mode) when none of the definitions were below the
+/// workspace roots, which usually means `definitions-path` is wrong.
+fn check_loaded(workspace: &Workspace, strict: bool) -> anyhow::Result<()> {
+    if !workspace.files.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!(
+        "No definitions were found below {:?}, so no API chapters were generated. \
+         Check that definitions-path points at the folder containing your LuaCATS definitions.",
+        workspace.roots
+    );
+    if strict {
+        return Err(anyhow::anyhow!(message));
+    }
+    warn!("{}", message);
+
+    Ok(())
+}
+
+/// Fail if two chapters have the same path, which mdbook requires to be
+/// unique, naming the chapters involved.
+fn check_unique_paths(book: &Book) -> anyhow::Result<()> {
+    let mut chapters: HashMap<&Path, Vec<&str>> = HashMap::new();
+    for item in book.iter() {
+        if let BookItem::Chapter(Chapter { name, path: Some(path), .. }) = item {
+            chapters.entry(path).or_default().push(name);
+        }
+    }
+
+    let conflicts: Vec<String> = chapters
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(path, names)| format!("{} ({})", path.display(), names.join(", ")))
+        .sorted()
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Multiple chapters share the same path: {}",
+            conflicts.join("; ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// A `SUMMARY.md` listing of the part titles and chapters, with sub chapters
+/// indented below their parent.
+fn summary_fragment(items: &[BookItem]) -> String {
+    fn push_items(fragment: &mut String, items: &[BookItem], depth: usize) {
+        for item in items {
+            match item {
+                BookItem::PartTitle(title) => fragment.push_str(&format!("\n# {}\n\n", title)),
+                BookItem::Chapter(chapter) => {
+                    let path = chapter.path.as_deref().unwrap_or(Path::new(""));
+                    fragment.push_str(&format!(
+                        "{}- [{}]({})\n",
+                        "    ".repeat(depth),
+                        chapter.name,
+                        path.to_string_lossy().replace('\\', "/")
+                    ));
+                    push_items(fragment, &chapter.sub_items, depth + 1);
+                }
+                BookItem::Separator => fragment.push_str("\n---\n\n"),
+            }
+        }
+    }
+
+    let mut fragment = String::new();
+    push_items(&mut fragment, items, 0);
+
+    fragment.trim_start().to_owned()
+}
+
+/// Add a part title followed by a top level chapter for each file.
+fn push_part(book: &mut Book, hbs: &Handlebars, options: &MarkdownOptions, nav_depth: Option<usize>, title: String, files: &[MetaFile]) -> anyhow::Result<()> {
+    book.push_item(BookItem::PartTitle(title));
+
+    for (index, file) in files.iter().enumerate() {
+        let chapter = build_chapter(hbs, options, nav_depth, file, index, None)?;
+        book.push_item(BookItem::Chapter(chapter));
+    }
+
+    Ok(())
+}
+
+/// Add each part from `load_parts`, taking its top level files from the
+/// front of `files` in turn.
+fn push_parts(book: &mut Book, hbs: &Handlebars, options: &MarkdownOptions, nav_depth: Option<usize>, parts: Vec<(String, usize)>, mut files: &[MetaFile]) -> anyhow::Result<()> {
+    for (title, len) in parts {
+        let (part, rest) = files.split_at(len);
+        push_part(book, hbs, options, nav_depth, title, part)?;
+        files = rest;
+    }
+
+    Ok(())
+}
+
+/// Fill in the book's chapters at the path of a generated chapter with its
+/// content, warning about the generated chapters the summary doesn't list.
+fn fill_chapters(book: &mut Book, generated: Book) {
+    let mut contents: HashMap<PathBuf, String> = generated
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(Chapter { path: Some(path), content, .. }) => Some((path.clone(), content.clone())),
+            _ => None,
+        })
+        .collect();
+
+    book.for_each_mut(|item| {
+        if let BookItem::Chapter(Chapter { path: Some(path), content, .. }) = item {
+            if let Some(generated) = contents.remove(path) {
+                *content = generated;
+            }
+        }
+    });
+
+    for path in contents.keys().sorted() {
+        warn!("{} isn't listed in SUMMARY.md, so it isn't in the book", path.display());
+    }
+}
+
+/// The number following the last numbered top level chapter in `items`.
+fn next_number(items: &[BookItem]) -> u32 {
+    items
+        .iter()
+        .rev()
+        .find_map(|item| match item {
+            BookItem::Chapter(Chapter { number: Some(number), .. }) => number.0.first().copied(),
+            _ => None,
+        })
+        .unwrap_or_default()
+        + 1
+}
+
+/// Build the chapter for a file, with a sub chapter for each sub file unless
+/// the chapter is already `nav_depth` levels deep, in which case the sub
+/// files are folded into it.
+fn build_chapter(hbs: &Handlebars, options: &MarkdownOptions, nav_depth: Option<usize>, file: &MetaFile, index: usize, parent: Option<&Chapter>) -> anyhow::Result<Chapter> {
+    let name = file.title();
+    let depth = parent.map_or(0, |parent| parent.parent_names.len() + 1) + 1;
+    let folded = nav_depth.is_some_and(|nav_depth| depth >= nav_depth);
+    let content = if folded {
+        markdown::render_folded(hbs, file, options)?
+    } else {
+        markdown::render_file(hbs, file, options)?
+    };
     let md_path = file.path.with_extension("md");
     let number = match parent {
         Some(parent) => {
@@ -132,6 +893,17 @@ fn build_chapter(hbs: &Handlebars, file: &MetaFile, index: usize, parent: Option
         None => Vec::new(),
     };
 
+    let content = if options.emit_index && !folded && !file.sub_files.is_empty() {
+        let index = markdown::render_index(hbs, file, options)?;
+        if content.trim().is_empty() {
+            index
+        } else {
+            format!("{}\n\n{}", content.trim_end(), index)
+        }
+    } else {
+        content
+    };
+
     let mut chapter = Chapter {
         name,
         content,
@@ -142,11 +914,24 @@ fn build_chapter(hbs: &Handlebars, file: &MetaFile, index: usize, parent: Option
         parent_names,
     };
 
+    if folded {
+        return Ok(chapter);
+    }
+
     chapter.sub_items = file.sub_files
         .iter()
+        .filter(|sub_file| {
+            // Sub files are always deeper than their parent, so anything else
+            // repeats an ancestor and would nest the same chapters forever.
+            let nested = sub_file.path.components().count() > file.path.components().count();
+            if !nested {
+                warn!("Skipping {} nested below {}, which would be a cycle", sub_file.path.display(), file.path.display());
+            }
+            nested
+        })
         .enumerate()
         .map(|(sub_index, sub_file)| -> anyhow::Result<BookItem> {
-            let chapter = build_chapter(hbs, sub_file, sub_index, Some(&chapter))?;
+            let chapter = build_chapter(hbs, options, nav_depth, sub_file, sub_index, Some(&chapter))?;
             Ok(BookItem::Chapter(chapter))
         })
         .collect::<anyhow::Result<Vec<BookItem>>>()?;
@@ -156,6 +941,7 @@ fn build_chapter(hbs: &Handlebars, file: &MetaFile, index: usize, parent: Option
 
 #[cfg(test)]
 mod test {
+
     use super::*;
 
     fn init() {
@@ -214,4 +1000,622 @@ mod test {
         // TODO: better asserts
         assert_eq!(actual_book.sections.len(), 2); // Chapter 1, Chapter "hello"
     }
+
+    #[test]
+    fn render_single_chapter() -> anyhow::Result<()> {
+        let definitions = serde_json::from_str(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "rawdesc": "Say hello.",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/hello.lua"}]
+        }]"#)?;
+        let file = MetaFile {
+            path: PathBuf::from("hello.lua"),
+            definitions,
+            ..Default::default()
+        };
+
+        let chapter = render_chapter(&file, &MarkdownOptions::default())?;
+
+        assert_eq!(chapter.name, "hello");
+        assert_eq!(chapter.path, Some(PathBuf::from("hello.md")));
+        assert!(chapter.content.starts_with("## hello { #hello }\n\nSay hello."), "{}", chapter.content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nav_depth() -> anyhow::Result<()> {
+        let definition = |name: &str, file: &str| -> anyhow::Result<Vec<Definition>> {
+            Ok(serde_json::from_str(&format!(r#"[{{
+                "name": "{}",
+                "type": "variable",
+                "defines": [{{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/{}"}}]
+            }}]"#, name, file))?)
+        };
+        let midi = MetaFile {
+            path: PathBuf::from("renoise/midi.lua"),
+            definitions: definition("renoise.Midi", "renoise/midi.lua")?,
+            ..Default::default()
+        };
+        let files = vec![MetaFile {
+            path: PathBuf::from("renoise.lua"),
+            definitions: definition("renoise", "renoise.lua")?,
+            sub_files: vec![midi],
+            ..Default::default()
+        }];
+
+        let symbols = SymbolIndex::with_nav_depth(&files, Some(1));
+        assert_eq!(symbols.get("renoise.Midi"), Some("renoise.md#renoise-midi"));
+
+        let hbs = markdown::registry(symbols)?;
+        for nav_depth in [0, 1] {
+            let chapter = build_chapter(&hbs, &MarkdownOptions::default(), Some(nav_depth), &files[0], 0, None)?;
+            assert!(chapter.sub_items.is_empty());
+            assert!(chapter.content.contains("## renoise.Midi { #renoise-midi }"), "{}", chapter.content);
+        }
+
+        let chapter = build_chapter(&hbs, &MarkdownOptions::default(), Some(2), &files[0], 0, None)?;
+        assert_eq!(chapter.sub_items.len(), 1);
+        assert!(!chapter.content.contains("renoise.Midi"), "{}", chapter.content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn collapse_single_child() -> anyhow::Result<()> {
+        let file = |path: &str, name: &str, sub_files: Vec<MetaFile>| -> anyhow::Result<MetaFile> {
+            Ok(MetaFile {
+                path: PathBuf::from(path),
+                definitions: serde_json::from_str(&format!(r#"[{{
+                    "name": "{}",
+                    "type": "variable",
+                    "defines": [{{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/{}"}}]
+                }}]"#, name, path))?,
+                sub_files,
+                ..Default::default()
+            })
+        };
+        let song = file("renoise/song.lua", "renoise.song", vec![
+            file("renoise/song/track.lua", "renoise.song.Track", Vec::new())?,
+            file("renoise/song/pattern.lua", "renoise.song.Pattern", Vec::new())?,
+        ])?;
+        let mut workspace = Workspace::default();
+        workspace.files.push(file("renoise.lua", "renoise", vec![song])?);
+
+        workspace.collapse_single_child();
+        let symbols = SymbolIndex::from_files(&workspace.files);
+        assert_eq!(symbols.get("renoise.song"), Some("renoise.md#renoise-song"));
+
+        let hbs = markdown::registry(symbols)?;
+        let chapter = build_chapter(&hbs, &MarkdownOptions::default(), None, &workspace.files[0], 0, None)?;
+        assert_eq!(chapter.name, "renoise/song");
+        assert!(chapter.content.contains("## renoise { #renoise }"), "{}", chapter.content);
+        assert!(chapter.content.contains("## renoise.song { #renoise-song }"), "{}", chapter.content);
+        assert_eq!(chapter.sub_items.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn collapse_single_child_sources() -> anyhow::Result<()> {
+        let file = |path: &str, name: &str, sub_files: Vec<MetaFile>| -> anyhow::Result<MetaFile> {
+            Ok(MetaFile {
+                path: PathBuf::from(path),
+                definitions: serde_json::from_str(&format!(r#"[{{
+                    "name": "{}",
+                    "type": "variable",
+                    "defines": [{{"start": 90004, "finish": 90010, "type": "setglobal", "file": "file:///library/{}"}}]
+                }}]"#, name, path))?,
+                sub_files,
+                source: PathBuf::from("/library").join(path),
+                ..Default::default()
+            })
+        };
+        let mut song = file("renoise/song.lua", "renoise.song", Vec::new())?;
+        song.also_defined_in.insert("renoise.song".into(), vec![PathBuf::from("renoise/extra.lua")]);
+        let mut renoise = file("renoise.lua", "renoise", vec![song])?;
+        renoise.also_defined_in.insert("renoise".into(), vec![PathBuf::from("extra.lua")]);
+        let mut workspace = Workspace::default();
+        workspace.files.push(renoise);
+
+        workspace.collapse_single_child();
+        let renoise = &workspace.files[0];
+        assert_eq!(renoise.also_defined_in.len(), 2);
+        assert_eq!(renoise.merged_sources.get(Path::new("renoise/song.lua")), Some(&PathBuf::from("/library/renoise/song.lua")));
+
+        let options = MarkdownOptions {
+            source_base_url: Some("https://github.com/me/repo/blob/main/library/".into()),
+            ..Default::default()
+        };
+        let markdown = markdown::render_file(&markdown::registry(SymbolIndex::new())?, renoise, &options)?;
+        assert!(markdown.contains("*Source: [renoise.lua:10](https://github.com/me/repo/blob/main/library/renoise.lua#L10)*"), "{}", markdown);
+        assert!(markdown.contains("*Source: [renoise/song.lua:10](https://github.com/me/repo/blob/main/library/renoise/song.lua#L10)*"), "{}", markdown);
+        assert!(markdown.contains("*Also defined in `renoise/extra.lua`*"), "{}", markdown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn directory_index() -> anyhow::Result<()> {
+        let file = |path: &str, names: &[&str], sub_files: Vec<MetaFile>| -> anyhow::Result<MetaFile> {
+            let definitions = names
+                .iter()
+                .map(|name| serde_json::from_str(&format!(r#"{{
+                    "name": "{}",
+                    "type": "variable",
+                    "defines": [{{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/{}"}}]
+                }}"#, name, path)))
+                .collect::<Result<Vec<Definition>, _>>()?;
+            Ok(MetaFile {
+                path: PathBuf::from(path),
+                definitions,
+                sub_files,
+                ..Default::default()
+            })
+        };
+        let renoise = file("renoise.lua", &[], vec![
+            file("renoise/midi.lua", &["renoise.Midi", "renoise.Midi.send"], Vec::new())?,
+            file("renoise/song.lua", &[], Vec::new())?,
+            file("renoise/pattern.lua", &["renoise.Pattern", "renoise.pattern"], Vec::new())?,
+        ])?;
+        let hbs = markdown::registry(SymbolIndex::from_files(std::slice::from_ref(&renoise)))?;
+        let options = MarkdownOptions {
+            emit_index: true,
+            ..Default::default()
+        };
+
+        let chapter = build_chapter(&hbs, &options, None, &renoise, 0, None)?;
+        assert_eq!(chapter.content, concat!(
+            "## Index { .h-index }\n\n",
+            "- [midi](renoise/midi.md) — [`renoise.Midi`](renoise/midi.md#renoise-midi), [`renoise.Midi.send`](renoise/midi.md#renoise-midi-send)\n",
+            "- [song](renoise/song.md)\n",
+            "- [pattern](renoise/pattern.md) — [`renoise.Pattern`](renoise/pattern.md#renoise-pattern), [`renoise.pattern`](renoise/pattern.md#renoise-pattern-1)\n",
+        ));
+
+        let chapter = build_chapter(&hbs, &MarkdownOptions::default(), None, &renoise, 0, None)?;
+        assert!(!chapter.content.contains("Index"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sub_file_cycle() -> anyhow::Result<()> {
+        let mut renoise = MetaFile {
+            path: PathBuf::from("renoise.lua"),
+            sub_files: vec![MetaFile {
+                path: PathBuf::from("renoise/midi.lua"),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ancestor = renoise.clone();
+        renoise.sub_files[0].sub_files.push(ancestor.clone());
+        renoise.sub_files.push(ancestor);
+
+        let hbs = markdown::registry(SymbolIndex::from_files(std::slice::from_ref(&renoise)))?;
+        let chapter = build_chapter(&hbs, &MarkdownOptions::default(), None, &renoise, 0, None)?;
+
+        assert_eq!(chapter.sub_items.len(), 1);
+        match &chapter.sub_items[0] {
+            BookItem::Chapter(midi) => assert!(midi.sub_items.is_empty()),
+            item => panic!("Expected a chapter, not {:?}", item),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn summary_fragment_chapters() {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new("renoise", String::new(), "renoise.md", Vec::new())));
+        book.push_item(BookItem::Chapter(Chapter::new("Intro", "Hello.".into(), "intro.md", Vec::new())));
+        let mut generated = Book::new();
+        generated.push_item(BookItem::Chapter(Chapter::new("renoise", "## renoise".into(), "renoise.md", Vec::new())));
+        generated.push_item(BookItem::Chapter(Chapter::new("midi", "## midi".into(), "renoise/midi.md", Vec::new())));
+
+        fill_chapters(&mut book, generated);
+
+        let chapters: Vec<(&str, &str)> = book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some((chapter.name.as_str(), chapter.content.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chapters, vec![("renoise", "## renoise"), ("Intro", "Hello.")]);
+    }
+
+    #[test]
+    fn summary_fragment_hierarchy() {
+        let midi = Chapter::new("midi", String::new(), "renoise/midi.md", vec!["renoise".into()]);
+        let mut renoise = Chapter::new("renoise", String::new(), "renoise.md", Vec::new());
+        renoise.sub_items.push(BookItem::Chapter(midi));
+        let items = vec![
+            BookItem::PartTitle("API Reference".into()),
+            BookItem::Chapter(renoise),
+            BookItem::Chapter(Chapter::new("bit", String::new(), "bit.md", Vec::new())),
+            BookItem::PartTitle("Standard Library".into()),
+            BookItem::Chapter(Chapter::new("string", String::new(), "stdlib/string.md", Vec::new())),
+        ];
+
+        assert_eq!(summary_fragment(&items), concat!(
+            "# API Reference\n\n",
+            "- [renoise](renoise.md)\n",
+            "    - [midi](renoise/midi.md)\n",
+            "- [bit](bit.md)\n",
+            "\n# Standard Library\n\n",
+            "- [string](stdlib/string.md)\n",
+        ));
+    }
+
+    #[test]
+    fn no_definitions_in_roots() -> anyhow::Result<()> {
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "print",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///luals/meta/basic.lua"}]
+        }]"#)?;
+
+        let mut workspace = Workspace::new("/library");
+        workspace.load(docs)?;
+
+        assert!(check_loaded(&workspace, false).is_ok());
+        let err = check_loaded(&workspace, true).unwrap_err();
+        assert!(err.to_string().contains("definitions-path"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_chapter_paths() {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new("bit", String::new(), "bit.md", Vec::new())));
+        book.push_item(BookItem::Chapter(Chapter::new("renoise", String::new(), "renoise.md", Vec::new())));
+        assert!(check_unique_paths(&book).is_ok());
+
+        book.push_item(BookItem::Chapter(Chapter::new("Bit Operations", String::new(), "bit.md", Vec::new())));
+        let err = check_unique_paths(&book).unwrap_err();
+        assert_eq!(err.to_string(), "Multiple chapters share the same path: bit.md (bit, Bit Operations)");
+    }
+
+    #[test]
+    fn part_title_placeholder() -> anyhow::Result<()> {
+        let mut table = Table::new();
+        table.insert("part-title".into(), Value::String("{title} API".into()));
+        table.insert("definitions-path".into(), Value::String("/library".into()));
+        let config = Config::try_from(Some(&table))?;
+
+        assert_eq!(config.part_title(None), "API");
+        assert_eq!(Config::default().part_title(Some("Renoise")), "API Reference");
+
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "song",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/song.lua"}]
+        }]"#)?;
+        let part_title = config.part_title(Some("Renoise"));
+        let (workspace, parts) = load_parts(&config, part_title, config.definitions_paths.clone(), docs)?;
+        let hbs = markdown::registry(SymbolIndex::from_files(&workspace.files))?;
+        let mut book = Book::new();
+        push_parts(&mut book, &hbs, &config.markdown, None, parts, &workspace.files)?;
+
+        assert!(matches!(book.sections.first(), Some(BookItem::PartTitle(title)) if title == "Renoise API"), "{:?}", book.sections);
+
+        Ok(())
+    }
+
+    #[test]
+    fn part_title_and_definitions_path() {
+        let mut table = Table::new();
+        table.insert("part-title".into(), Value::String("Renoise API".into()));
+        table.insert("definitions-path".into(), Value::String("definitions".into()));
+        let config = Config::try_from(Some(&table)).unwrap();
+
+        assert_eq!(config.part_title, Some("Renoise API".into()));
+        assert_eq!(config.definitions_paths, vec![PathBuf::from("definitions")]);
+    }
+
+    #[test]
+    fn heading_level() {
+        let mut table = Table::new();
+        table.insert("heading-level".into(), Value::Integer(3));
+        assert_eq!(Config::try_from(Some(&table)).unwrap().markdown.heading_level(), 3);
+
+        table.insert("heading-level".into(), Value::Integer(7));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "heading-level must be a number from 1 to 6, not 7");
+
+        assert_eq!(Config::try_from(None).unwrap().markdown.heading_level(), 2);
+    }
+
+    #[test]
+    fn show_protected() {
+        assert!(!Config::try_from(None).unwrap().hide_protected);
+
+        let mut table = Table::new();
+        table.insert("show-protected".into(), Value::Boolean(false));
+        assert!(Config::try_from(Some(&table)).unwrap().hide_protected);
+    }
+
+    #[test]
+    fn show_private() {
+        assert!(!Config::try_from(None).unwrap().show_private);
+
+        let mut table = Table::new();
+        table.insert("show-private".into(), Value::Boolean(true));
+        assert!(Config::try_from(Some(&table)).unwrap().show_private);
+
+        let mut table = Table::new();
+        table.insert("include-private".into(), Value::Boolean(true));
+        assert!(Config::try_from(Some(&table)).unwrap().show_private);
+    }
+
+    #[test]
+    fn params_in_heading_config() {
+        let mut table = Table::new();
+        table.insert("params-in-heading".into(), Value::Integer(2));
+        assert_eq!(Config::try_from(Some(&table)).unwrap().markdown.params_in_heading, Some(2));
+
+        table.insert("params-in-heading".into(), Value::Integer(-1));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "params-in-heading must be a number of parameters, not -1");
+
+        table.insert("params-in-heading".into(), Value::String("2".into()));
+        assert!(Config::try_from(Some(&table)).is_err());
+    }
+
+    #[test]
+    fn union_list_threshold_config() {
+        let mut table = Table::new();
+        table.insert("union-list-threshold".into(), Value::Integer(3));
+        assert_eq!(Config::try_from(Some(&table)).unwrap().markdown.union_list_threshold, Some(3));
+
+        table.insert("union-list-threshold".into(), Value::Boolean(true));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "union-list-threshold must be a number of types, not true");
+    }
+
+    #[test]
+    fn nav_depth_config() {
+        let mut table = Table::new();
+        table.insert("nav-depth".into(), Value::Integer(2));
+        assert_eq!(Config::try_from(Some(&table)).unwrap().nav_depth, Some(2));
+
+        table.insert("nav-depth".into(), Value::Integer(300));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "nav-depth must be a number from 0 to 255, not 300");
+    }
+
+    #[test]
+    fn lua_ls_timeout() {
+        let mut table = Table::new();
+        table.insert("lua-ls-timeout".into(), Value::Integer(30));
+        assert_eq!(Config::try_from(Some(&table)).unwrap().lua_ls_timeout, Some(Duration::from_secs(30)));
+
+        table.insert("lua-ls-timeout".into(), Value::Integer(0));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "lua-ls-timeout must be a number of seconds, not 0");
+    }
+
+    #[test]
+    fn cache_docs() {
+        assert!(!Config::try_from(None).unwrap().skip_doc_cache);
+        assert!(!Config::try_from(Some(&Table::new())).unwrap().skip_doc_cache);
+
+        let mut table = Table::new();
+        table.insert("cache-docs".into(), Value::Boolean(false));
+        assert!(Config::try_from(Some(&table)).unwrap().skip_doc_cache);
+
+        table.insert("cache-docs".into(), Value::String("no".into()));
+        let err = Config::try_from(Some(&table)).unwrap_err();
+        assert_eq!(err.to_string(), "cache-docs must be true or false, not \"no\"");
+    }
+
+    #[test]
+    fn name_prefix() -> anyhow::Result<()> {
+        let mut docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "renoise.song",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/renoise.lua"}]
+        }, {
+            "name": "renoise.song.tracks",
+            "type": "variable",
+            "defines": [{"start": 20, "finish": 30, "type": "setglobal", "file": "file:///library/renoise.lua"}]
+        }, {
+            "name": "renoise.app",
+            "type": "variable",
+            "defines": [{"start": 40, "finish": 50, "type": "setglobal", "file": "file:///library/renoise.lua"}]
+        }]"#)?;
+        let mut table = Table::new();
+        table.insert("name-prefix".into(), Value::String("renoise.song".into()));
+        let config = Config::try_from(Some(&table))?;
+
+        config.retain_docs(&mut docs);
+        let mut workspace = Workspace::new("/library");
+        workspace.load(docs)?;
+        let markdown = workspace.render_file_markdown("renoise.lua", &config.markdown)?;
+
+        assert!(markdown.contains("## renoise.song { #renoise-song }"), "{}", markdown);
+        assert!(markdown.contains("## renoise.song.tracks { #renoise-song-tracks }"), "{}", markdown);
+        assert!(!markdown.contains("renoise.app"), "{}", markdown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedupe_docs_across_roots() -> anyhow::Result<()> {
+        let mut docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "print",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///meta/basic.lua"}]
+        }, {
+            "name": "song",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///core/song.lua"}]
+        }, {
+            "name": "print",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///meta/basic.lua"}]
+        }, {
+            "name": "print",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///extras/print.lua"}]
+        }]"#)?;
+
+        dedupe_docs(&mut docs);
+
+        let files: Vec<&str> = docs.iter().map(|definition| definition.defines[0].file.as_str()).collect();
+        assert_eq!(files, vec!["file:///meta/basic.lua", "file:///core/song.lua", "file:///extras/print.lua"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn include_exclude_globs() {
+        let mut table = Table::new();
+        table.insert("exclude".into(), Value::Array(vec!["internal/**".into(), "*_private.lua".into()]));
+        let config = Config::try_from(Some(&table)).unwrap();
+        assert!(config.is_file_included(Path::new("renoise.lua")));
+        assert!(!config.is_file_included(Path::new("internal/util.lua")));
+        assert!(!config.is_file_included(Path::new("renoise/song_private.lua")));
+
+        table.insert("include".into(), Value::String("renoise/**".into()));
+        let config = Config::try_from(Some(&table)).unwrap();
+        assert!(!config.is_file_included(Path::new("bit.lua")));
+        assert!(config.is_file_included(Path::new("renoise/song.lua")));
+        assert!(!config.is_file_included(Path::new("renoise/song_private.lua")));
+
+        table.insert("include".into(), Value::String("[".into()));
+        assert!(Config::try_from(Some(&table)).is_err());
+    }
+
+    #[test]
+    fn stdlib_part() -> anyhow::Result<()> {
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "hello",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///library/hello.lua"}]
+        }, {
+            "name": "print",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///luals/meta/basic.lua"}]
+        }]"#)?;
+        let roots = vec![PathBuf::from("/library")];
+
+        let stdlib = Workspace::external(&docs, &roots, "stdlib")?;
+        let mut workspace = Workspace::with_roots(roots);
+        workspace.load(docs)?;
+
+        let hbs = markdown::registry(SymbolIndex::new())?;
+        let options = MarkdownOptions::default();
+        let mut book = Book::new();
+        push_part(&mut book, &hbs, &options, None, "API Reference".into(), &workspace.files)?;
+        push_part(&mut book, &hbs, &options, None, "Standard Library".into(), &stdlib.files)?;
+
+        let items: Vec<String> = book.sections.iter()
+            .map(|item| match item {
+                BookItem::PartTitle(title) => format!("# {}", title),
+                BookItem::Chapter(chapter) => format!("{:?}", chapter.path.as_ref().unwrap()),
+                BookItem::Separator => "---".into(),
+            })
+            .collect();
+
+        assert_eq!(items, vec!["# API Reference", "\"hello.md\"", "# Standard Library", "\"stdlib/meta/basic.md\""]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn part_per_root() -> anyhow::Result<()> {
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "song",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///core/song.lua"}]
+        }, {
+            "name": "midi",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///extras/midi.lua"}]
+        }, {
+            "name": "osc",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///extras/osc.lua"}]
+        }]"#)?;
+        let mut table = Table::new();
+        let mut extras = Table::new();
+        extras.insert("path".into(), Value::String("/extras".into()));
+        extras.insert("title".into(), Value::String("Extras".into()));
+        table.insert("definitions-path".into(), Value::Array(vec!["/core".into(), Value::Table(extras)]));
+        table.insert("part-per-root".into(), Value::Boolean(true));
+        let config = Config::try_from(Some(&table))?;
+
+        let (workspace, parts) = load_parts(&config, "API Reference".into(), config.definitions_paths.clone(), docs)?;
+        assert_eq!(parts, vec![("core".into(), 1), ("Extras".into(), 2)]);
+
+        let hbs = markdown::registry(SymbolIndex::from_files(&workspace.files))?;
+        let mut book = Book::new();
+        push_parts(&mut book, &hbs, &config.markdown, None, parts, &workspace.files)?;
+
+        let items: Vec<String> = book.sections.iter()
+            .map(|item| match item {
+                BookItem::PartTitle(title) => format!("# {}", title),
+                BookItem::Chapter(chapter) => format!("{:?}", chapter.path.as_ref().unwrap()),
+                BookItem::Separator => "---".into(),
+            })
+            .collect();
+        assert_eq!(items, vec!["# core", "\"core/song.md\"", "# Extras", "\"extras/midi.md\"", "\"extras/osc.md\""]);
+        // A glossary would follow on from the last part
+        assert_eq!(next_number(&book.sections), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn part_per_root_shared_paths() -> anyhow::Result<()> {
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "core",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///core/init.lua"}]
+        }, {
+            "name": "extras",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///extras/init.lua"}]
+        }, {
+            "name": "song",
+            "type": "variable",
+            "defines": [{"start": 0, "finish": 10, "type": "setglobal", "file": "file:///core/song.lua"}]
+        }, {
+            "name": "midi",
+            "type": "variable",
+            "defines": [
+                {"start": 20, "finish": 30, "type": "setglobal", "file": "file:///core/song.lua"},
+                {"start": 0, "finish": 10, "type": "setglobal", "file": "file:///extras/midi.lua"}
+            ]
+        }]"#)?;
+        let mut table = Table::new();
+        table.insert("definitions-path".into(), Value::Array(vec!["/core".into(), "/extras".into()]));
+        table.insert("part-per-root".into(), Value::Boolean(true));
+        let config = Config::try_from(Some(&table))?;
+
+        let (workspace, parts) = load_parts(&config, "API Reference".into(), config.definitions_paths.clone(), docs)?;
+        assert_eq!(parts, vec![("core".into(), 1), ("extras".into(), 2)]);
+
+        let hbs = markdown::registry(SymbolIndex::from_files(&workspace.files))?;
+        let mut book = Book::new();
+        push_parts(&mut book, &hbs, &config.markdown, None, parts, &workspace.files)?;
+        check_unique_paths(&book)?;
+
+        let chapters: Vec<&Chapter> = book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter),
+                _ => None,
+            })
+            .collect();
+        let paths: Vec<String> = chapters.iter().map(|chapter| format!("{:?}", chapter.path.as_ref().unwrap())).collect();
+        assert_eq!(paths, vec!["\"core/song.md\"", "\"extras/init.md\"", "\"extras/midi.md\""]);
+        // The midi symbol is only rendered from the root with priority
+        assert!(!chapters[0].content.contains("midi"), "{}", chapters[0].content);
+        assert!(chapters[1].content.contains("core") && chapters[1].content.contains("extras"), "{}", chapters[1].content);
+
+        Ok(())
+    }
 }
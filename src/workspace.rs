@@ -1,56 +1,224 @@
 use std::{
-    collections::HashMap, path::PathBuf
+    collections::{BTreeMap, HashMap, HashSet}, fs, path::{Path, PathBuf}, process::Command
 };
 
 use anyhow::{anyhow, Ok};
 use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::lua_cats::Definition;
+use crate::{
+    links::SymbolIndex,
+    lua_cats::{Definition, DefinitionType, Field, Kind, Visibility},
+    markdown::{self, MarkdownOptions},
+};
 
 /// A folder containing LuaCats definition files.
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Default)]
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub struct Workspace {
-    /// The absolute path to the root folder of the workspace.
+    /// The absolute path to the primary root folder of the workspace, the
+    /// first of `roots`.
+    #[serde(default)]
     pub root: PathBuf,
+    /// The absolute paths to the root folders of the workspace, in priority
+    /// order. When the same symbol is defined below more than one root the
+    /// definition from the last root wins.
+    pub roots: Vec<PathBuf>,
     /// The workspace's meta files.
     pub files: Vec<MetaFile>,
 }
 
 impl Workspace {
     pub fn new<P: Into<PathBuf>>(p: P) -> Self {
+        Self::with_roots([p])
+    }
+
+    /// Create a workspace spanning multiple roots, lowest priority first.
+    pub fn with_roots<I, P>(roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let roots: Vec<PathBuf> = roots.into_iter().map(Into::into).collect();
         Self {
-            root: p.into(),
+            root: roots.first().cloned().unwrap_or_default(),
+            roots,
             ..Default::default()
         }
     }
 
+    /// Create a workspace of the definitions from outside the given roots,
+    /// such as the LuaLS standard library.
+    ///
+    /// Each outermost folder containing such definitions becomes a root of
+    /// its own, and its files are placed below `prefix` and the folder's name
+    /// so their chapters can't collide with the files from `roots` or with
+    /// the same file name in another folder. When more than one folder
+    /// defines a symbol the definition from the last folder wins.
+    pub fn external<P: AsRef<Path>>(docs: &[Definition], roots: &[PathBuf], prefix: P) -> anyhow::Result<Self> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let mut external_docs: Vec<(Vec<PathBuf>, Definition)> = Vec::new();
+
+        for definition in docs.iter() {
+            let mut definition = definition.clone();
+            let mut defines = Vec::new();
+            let mut file_paths = Vec::new();
+            for define in definition.defines.into_iter() {
+                let file_path = Url::parse(&define.file)?
+                    .to_file_path()
+                    .map_err(|_| anyhow!("inalid file url"))?;
+                if roots.iter().any(|root| file_path.starts_with(root)) {
+                    continue;
+                }
+                if let Some(dir) = file_path.parent() {
+                    if !dirs.iter().any(|d| d == dir) {
+                        dirs.push(dir.to_owned());
+                    }
+                }
+                file_paths.push(file_path);
+                defines.push(define);
+            }
+            if !defines.is_empty() {
+                definition.defines = defines;
+                external_docs.push((file_paths, definition));
+            }
+        }
+
+        // A folder's sub folders are loaded along with it
+        let mut outer_dirs: Vec<PathBuf> = Vec::new();
+        for dir in dirs.into_iter().sorted() {
+            if !outer_dirs.iter().any(|outer| dir.starts_with(outer)) {
+                outer_dirs.push(dir);
+            }
+        }
+
+        let mut names: HashMap<String, usize> = HashMap::new();
+        let folders: Vec<PathBuf> = outer_dirs
+            .iter()
+            .map(|dir| {
+                let name = dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                let count = names.entry(name.clone()).or_default();
+                *count += 1;
+                match *count {
+                    1 => prefix.as_ref().join(name),
+                    count => prefix.as_ref().join(format!("{}-{}", name, count)),
+                }
+            })
+            .collect();
+
+        // Load the folders last first, so each symbol is only placed in the
+        // last folder defining it
+        let mut placed: HashSet<String> = HashSet::new();
+        let mut loaded: Vec<Vec<MetaFile>> = Vec::new();
+        for (dir, folder) in outer_dirs.iter().zip(folders.iter()).rev() {
+            let dir_docs = external_docs
+                .iter()
+                .filter(|(_, definition)| !placed.contains(&definition.name))
+                .filter(|(file_paths, _)| file_paths.iter().any(|file_path| file_path.starts_with(dir)))
+                .map(|(_, definition)| definition.clone())
+                .collect_vec();
+            placed.extend(dir_docs.iter().map(|definition| definition.name.clone()));
+
+            let mut dir_workspace = Self::new(dir);
+            dir_workspace.load(dir_docs)?;
+            for file in dir_workspace.files.iter_mut() {
+                file.prefix(folder);
+            }
+            loaded.push(dir_workspace.files);
+        }
+
+        let mut workspace = Self::with_roots(outer_dirs);
+        workspace.files = loaded.into_iter().rev().flatten().collect();
+
+        Ok(workspace)
+    }
+
     /// Load the workspace from the given doc definitions.
     pub fn load(&mut self, docs: Vec<Definition>) -> anyhow::Result<()> {
-        // Index definitions by their file in the order they were defined
-        let mut defs_by_file: HashMap<PathBuf, Vec<(u64, Definition)>> = HashMap::new();
+        // Resolve every define to its root, discarding definitions from outside
+        // the roots (system definitions)
+        let mut located: Vec<(usize, PathBuf, PathBuf, u64, Definition)> = Vec::new();
+        // For each located define, the index of its definition and whether it
+        // declares a type
+        let mut origins: Vec<(usize, bool)> = Vec::new();
 
-        for definition in docs.into_iter() {
+        for (index, definition) in docs.into_iter().enumerate() {
             for define in definition.defines.iter() {
                 let file_url = Url::parse(&define.file)?;
                 let file_path = file_url
                     .to_file_path()
                     .map_err(|_| anyhow!("inalid file url"))?;
-                defs_by_file
-                    .entry(file_path)
+                if let Some((priority, path)) = self.relative_path(&file_path) {
+                    located.push((priority, path, file_path, define.start, definition.clone()));
+                    origins.push((index, matches!(define.lua_type, DefinitionType::DocClass | DefinitionType::DocAlias | DefinitionType::DocEnum)));
+                }
+            }
+        }
+
+        // Only keep each symbol from the highest priority root defining it
+        let mut priorities: HashMap<String, usize> = HashMap::new();
+        for (priority, _, _, _, definition) in located.iter() {
+            let max = priorities.entry(definition.name.clone()).or_default();
+            *max = (*max).max(*priority);
+        }
+
+        // Render each symbol on a single page: the file declaring it with a
+        // `---@class`, `---@alias` or `---@enum`, or else the first defining it
+        let mut primaries: HashMap<usize, (bool, PathBuf)> = HashMap::new();
+        for ((priority, path, _, _, definition), (index, declared)) in located.iter().zip(origins.iter().copied()) {
+            if priorities[&definition.name] != *priority {
+                continue;
+            }
+            let primary = primaries.entry(index).or_insert((declared, path.clone()));
+            if declared && !primary.0 {
+                *primary = (declared, path.clone());
+            }
+        }
+
+        // Index definitions by their file in the order they were defined
+        let mut defs_by_file: HashMap<PathBuf, Vec<(u64, Definition)>> = HashMap::new();
+        let mut sources: HashMap<PathBuf, (usize, PathBuf)> = HashMap::new();
+        let mut placed: HashSet<usize> = HashSet::new();
+        let mut also_defined_in: HashMap<PathBuf, BTreeMap<String, Vec<PathBuf>>> = HashMap::new();
+
+        for ((priority, path, source, start, definition), (index, _)) in located.into_iter().zip(origins) {
+            if priorities[&definition.name] != priority {
+                continue;
+            }
+            let primary = &primaries[&index].1;
+            if *primary != path {
+                let others = also_defined_in
+                    .entry(primary.clone())
                     .or_default()
-                    .push((define.start, definition.clone()));
+                    .entry(definition.name.clone())
+                    .or_default();
+                if !others.contains(&path) {
+                    others.push(path);
+                }
+                continue;
+            }
+            if !placed.insert(index) {
+                continue;
+            }
+            let entry = sources.entry(path.clone()).or_insert((priority, source.clone()));
+            if entry.0 < priority {
+                *entry = (priority, source);
             }
+            defs_by_file
+                .entry(path)
+                .or_default()
+                .push((start, definition));
         }
 
-        let root = &self.root;
         let meta_files: Vec<MetaFile> = defs_by_file
             .into_iter()
-            .filter_map(|(path, definitions)| {
-                path.strip_prefix(root)
-                    .ok() // discard definitions from outside the root (system definitions)
-                    .map(|path| MetaFile::from((path.to_owned(), definitions)))
+            .map(|(path, definitions)| {
+                let source = sources.remove(&path).map(|(_, source)| source);
+                let also_defined_in = also_defined_in.remove(&path).unwrap_or_default();
+                let mut file = MetaFile::from((path, definitions));
+                file.source = source.unwrap_or_default();
+                file.also_defined_in = also_defined_in;
+                file
             })
             .sorted_by(|a, b| {
                 a.depth
@@ -66,6 +234,164 @@ impl Workspace {
         Ok(())
     }
 
+    /// Remove the definitions and fields annotated as `@private`, `@package`
+    /// or `@hidden`, along with any files left empty.
+    pub fn remove_hidden(&mut self) {
+        self.retain_symbols(&|definition| !definition.is_hidden(), &|field| !field.is_hidden());
+    }
+
+    /// Remove the definitions and fields annotated as `@protected`, along
+    /// with any files left empty.
+    pub fn remove_protected(&mut self) {
+        self.retain_symbols(
+            &|definition| definition.visibility() != Visibility::Protected,
+            &|field| field.visibility() != Visibility::Protected,
+        );
+    }
+
+    fn retain_symbols(&mut self, definition: &dyn Fn(&Definition) -> bool, field: &dyn Fn(&Field) -> bool) {
+        for file in self.files.iter_mut() {
+            file.retain_symbols(definition, field);
+        }
+        self.files.retain(|file| !file.is_empty());
+    }
+
+    /// Drop the files whose path relative to their root isn't kept by the
+    /// filter. The sub files of a dropped file are still filtered on their
+    /// own and take its place when kept.
+    pub fn retain_files<F: Fn(&Path) -> bool>(&mut self, keep: F) {
+        self.files = retain_files(std::mem::take(&mut self.files), &keep);
+    }
+
+    /// Merge every file with a single sub file into it, so a chain of
+    /// directories with one file each becomes one chapter.
+    pub fn collapse_single_child(&mut self) {
+        for file in self.files.iter_mut() {
+            file.collapse_single_child();
+        }
+    }
+
+    /// Take the top level files, grouped by the root their source is below,
+    /// in root order. Files whose source isn't below any root go with the
+    /// first.
+    pub fn split_by_root(&mut self) -> Vec<Vec<MetaFile>> {
+        let mut groups: Vec<Vec<MetaFile>> = self.roots.iter().map(|_| Vec::new()).collect();
+        for file in std::mem::take(&mut self.files).into_iter() {
+            let index = self.relative_path(&file.source).map(|(priority, _)| priority).unwrap_or_default();
+            if let Some(group) = groups.get_mut(index) {
+                group.push(file);
+            }
+        }
+        groups
+    }
+
+    /// Write the workspace's roots and file hierarchy, with every file's
+    /// definitions, to a JSON file for other tools to consume.
+    pub fn export_json<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+
+        Ok(())
+    }
+
+    /// Render every file to a markdown file below `dir`, mirroring the
+    /// workspace layout.
+    ///
+    /// Each chapter is written as soon as it's rendered rather than building
+    /// the whole book in memory first, so huge workspaces stay cheap.
+    pub fn write_markdown<P: AsRef<Path>>(&self, dir: P, options: &MarkdownOptions) -> anyhow::Result<()> {
+        let hbs = markdown::registry(SymbolIndex::from_files(&self.files))?;
+
+        let mut pending: Vec<&MetaFile> = self.files.iter().rev().collect();
+        while let Some(file) = pending.pop() {
+            let path = dir.as_ref().join(file.path.with_extension("md"));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, markdown::render_file(&hbs, file, options)?)?;
+
+            pending.extend(file.sub_files.iter().rev());
+        }
+
+        Ok(())
+    }
+
+    /// Every definition in reading order: files in path order with each
+    /// file's sub files after it, and each file's definitions in source order.
+    pub fn definitions_in_source_order(&self) -> Vec<&Definition> {
+        let mut definitions = Vec::new();
+
+        let mut pending: Vec<&MetaFile> = self.files.iter().sorted_by(|a, b| b.path.cmp(&a.path)).collect();
+        while let Some(file) = pending.pop() {
+            definitions.extend(
+                file.definitions
+                    .iter()
+                    .sorted_by_key(|definition| definition.defines.first().map(|define| define.start)),
+            );
+            pending.extend(file.sub_files.iter().sorted_by(|a, b| b.path.cmp(&a.path)));
+        }
+
+        definitions
+    }
+
+    /// Render every definition into one document, in global source order.
+    pub fn render_markdown(&self, options: &MarkdownOptions) -> anyhow::Result<String> {
+        let file = MetaFile {
+            path: PathBuf::from("index.lua"),
+            definitions: self.definitions_in_source_order().into_iter().cloned().collect(),
+            ..Default::default()
+        };
+        let hbs = markdown::registry(SymbolIndex::from_files(std::slice::from_ref(&file)))?;
+
+        markdown::render_file(&hbs, &file, options)
+    }
+
+    /// Find a file by its path, either relative to the workspace roots like
+    /// `renoise/midi.lua` or including a root.
+    pub fn find_file<P: AsRef<Path>>(&self, path: P) -> Option<&MetaFile> {
+        let path = path.as_ref();
+        let path = self
+            .relative_path(path)
+            .map(|(_, path)| path)
+            .unwrap_or_else(|| path.to_owned());
+
+        let mut pending: Vec<&MetaFile> = self.files.iter().collect();
+        while let Some(file) = pending.pop() {
+            if file.path == path {
+                return Some(file);
+            }
+            pending.extend(file.sub_files.iter());
+        }
+
+        None
+    }
+
+    /// Render a single file, without its sub files, linking types documented
+    /// anywhere in the workspace.
+    pub fn render_file_markdown<P: AsRef<Path>>(&self, path: P, options: &MarkdownOptions) -> anyhow::Result<String> {
+        let path = path.as_ref();
+        let file = self
+            .find_file(path)
+            .ok_or_else(|| anyhow!("No definitions were found in {}", path.display()))?;
+        let hbs = markdown::registry(SymbolIndex::from_files(&self.files))?;
+
+        markdown::render_file(&hbs, file, options)
+    }
+
+    /// Find the root containing the path, returning the root's priority and
+    /// the path relative to it.
+    fn relative_path(&self, path: &Path) -> Option<(usize, PathBuf)> {
+        self.roots
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(priority, root)| {
+                path.strip_prefix(root)
+                    .ok()
+                    .map(|path| (priority, path.to_owned()))
+            })
+    }
+
     fn add_file(&mut self, file: MetaFile) {
         let depth = file.depth;
 
@@ -77,8 +403,7 @@ impl Workspace {
 
         // Otherwise we have to find the file's parent
         for other_file in self.files.iter_mut() {
-            if other_file.depth == depth - 1 && other_file.file_stem() == file.directory_name().unwrap()
-            {
+            if other_file.depth == depth - 1 && Some(other_file.file_stem()) == file.directory_name() {
                 other_file.add_sub_file(file);
                 return;
             }
@@ -94,7 +419,7 @@ impl Workspace {
 
 
 /// A Lua file containing only LuaCats meta.
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Default, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub struct MetaFile {
     /// The file path, relative to the workspace root.
     pub path: PathBuf,
@@ -104,6 +429,18 @@ pub struct MetaFile {
     pub depth: usize,
     /// Files considered below this one in the heirarchy.
     pub sub_files: Vec<MetaFile>,
+    /// The absolute path to the file on disk.
+    pub source: PathBuf,
+    /// The chapter title, if it isn't the file stem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The other files defining this file's symbols, by symbol name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub also_defined_in: BTreeMap<String, Vec<PathBuf>>,
+    /// The files merged into this one by `collapse-single-child`, by their
+    /// path relative to the workspace root, with their absolute path on disk.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub merged_sources: BTreeMap<PathBuf, PathBuf>,
 }
 
 impl MetaFile {
@@ -116,16 +453,12 @@ impl MetaFile {
             return None;
         }
 
-        let dirname = self.path.parent().unwrap();
-
-        Some(
-            dirname
-                .strip_prefix(dirname.parent().unwrap())
-                .unwrap()
-                .as_os_str()
-                .to_string_lossy()
-                .into_owned(),
-        )
+        // A path like `song.lua` has an empty parent with no name, even if
+        // the depth claims otherwise.
+        self.path
+            .parent()?
+            .file_name()
+            .map(|dirname| dirname.to_string_lossy().into_owned())
     }
 
     pub fn file_name(&self) -> String {
@@ -144,9 +477,144 @@ impl MetaFile {
             .into_owned()
     }
 
+    /// The chapter title, which defaults to the module's name or else the
+    /// file stem.
+    pub fn title(&self) -> String {
+        self.title
+            .clone()
+            .or_else(|| self.module_name().map(str::to_owned))
+            .unwrap_or_else(|| self.file_stem())
+    }
+
+    /// The name of the class or global table the file documents, like
+    /// `renoise.song`, if every other definition in it is nested below.
+    pub fn module_name(&self) -> Option<&str> {
+        self.definitions
+            .iter()
+            .filter(|definition| definition.kind() == Kind::Class || definition.is_table())
+            .map(|definition| definition.name.as_str())
+            .find(|name| {
+                self.definitions.iter().all(|definition| {
+                    definition.name == *name
+                        || definition
+                            .name
+                            .strip_prefix(name)
+                            .is_some_and(|rest| rest.starts_with(['.', ':']))
+                })
+            })
+    }
+
     pub fn add_sub_file(&mut self, file: MetaFile) {
         self.sub_files.push(file)
     }
+
+    /// The number of definitions in the file and its sub files.
+    pub fn total_definitions(&self) -> usize {
+        self.definitions.len() + self.sub_files.iter().map(MetaFile::total_definitions).sum::<usize>()
+    }
+
+    /// Whether the file and its sub files have no definitions.
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty() && self.sub_files.iter().all(MetaFile::is_empty)
+    }
+
+    fn collapse_single_child(&mut self) {
+        while self.sub_files.len() == 1 {
+            let child = self.sub_files.remove(0);
+            self.title = Some(match child.module_name() {
+                Some(name) => name.to_owned(),
+                None => format!("{}/{}", self.title(), child.title()),
+            });
+            self.definitions.extend(child.definitions);
+            for (name, paths) in child.also_defined_in {
+                let others = self.also_defined_in.entry(name).or_default();
+                for path in paths {
+                    if !others.contains(&path) {
+                        others.push(path);
+                    }
+                }
+            }
+            self.merged_sources.insert(child.path, child.source);
+            self.merged_sources.extend(child.merged_sources);
+            self.sub_files = child.sub_files;
+        }
+
+        for file in self.sub_files.iter_mut() {
+            file.collapse_single_child();
+        }
+    }
+
+    fn retain_symbols(&mut self, definition: &dyn Fn(&Definition) -> bool, field: &dyn Fn(&Field) -> bool) {
+        self.definitions.retain(|def| definition(def));
+        for def in self.definitions.iter_mut() {
+            def.fields.retain(|f| field(f));
+        }
+
+        for sub_file in self.sub_files.iter_mut() {
+            sub_file.retain_symbols(definition, field);
+        }
+        self.sub_files.retain(|file| !file.is_empty());
+    }
+
+    /// Move the file and its sub files below the given folder.
+    pub fn prefix(&mut self, prefix: &Path) {
+        self.path = prefix.join(&self.path);
+
+        for sub_file in self.sub_files.iter_mut() {
+            sub_file.prefix(prefix);
+        }
+    }
+
+    /// The date the source file was last changed, formatted as `YYYY-MM-DD`.
+    ///
+    /// When `use_git` is set the date of the last commit touching the file is
+    /// preferred, falling back to the filesystem mtime if the file isn't
+    /// tracked or git isn't available.
+    pub fn last_modified(&self, use_git: bool) -> Option<String> {
+        if use_git {
+            if let Some(date) = self.git_last_modified() {
+                return Some(date);
+            }
+        }
+
+        let mtime = fs::metadata(&self.source).and_then(|m| m.modified()).ok()?;
+        let timestamp = humantime::format_rfc3339(mtime).to_string();
+
+        Some(timestamp[..10].to_owned())
+    }
+
+    fn git_last_modified(&self) -> Option<String> {
+        let output = Command::new("git")
+            .arg("log")
+            .arg("-1")
+            .arg("--format=%cs")
+            .arg("--")
+            .arg(&self.source)
+            .current_dir(self.source.parent()?)
+            .output()
+            .ok()?;
+
+        let date = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        if !output.status.success() || date.is_empty() {
+            return None;
+        }
+
+        Some(date)
+    }
+}
+
+fn retain_files<F: Fn(&Path) -> bool>(files: Vec<MetaFile>, keep: &F) -> Vec<MetaFile> {
+    let mut kept = Vec::new();
+    for mut file in files.into_iter() {
+        let sub_files = retain_files(std::mem::take(&mut file.sub_files), keep);
+        if keep(&file.path) {
+            file.sub_files = sub_files;
+            kept.push(file);
+        } else {
+            kept.extend(sub_files);
+        }
+    }
+    kept
 }
 
 impl From<(PathBuf, Vec<(u64, Definition)>)> for MetaFile {
@@ -173,6 +641,10 @@ impl From<(PathBuf, Vec<(u64, Definition)>)> for MetaFile {
 #[cfg(test)]
 mod test {
 
+    use std::collections::BTreeMap;
+
+    use tempdir::TempDir;
+
     use crate::lua_cats::{Define, DefinitionType};
 
     use super::*;
@@ -181,6 +653,7 @@ mod test {
         Definition {
         desc: None,
         rawdesc: None,
+        rawdescs: BTreeMap::new(),
         name: "test".into(),
         lua_type: DefinitionType::Nil,
         fields: Vec::new(),
@@ -191,10 +664,13 @@ mod test {
             file: file.into(),
             extends: Vec::new(),
         }],
+        deprecated: false,
+        visible: None,
         }
     }
 
     #[test]
+    #[allow(clippy::useless_vec, clippy::get_first)]
     fn load_workspace() -> anyhow::Result<()> {
         let file_urls = vec![
             "file:///my/definitions/path/standard.lua",
@@ -222,4 +698,343 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn module_titles() -> anyhow::Result<()> {
+        let file = |json: &str| -> anyhow::Result<MetaFile> {
+            Ok(MetaFile {
+                path: PathBuf::from("renoise/song.lua"),
+                definitions: serde_json::from_str(json)?,
+                ..Default::default()
+            })
+        };
+        let table = |name: &str| format!(r#"{{
+            "name": "{}",
+            "type": "variable",
+            "defines": [{{
+                "start": 0,
+                "finish": 10,
+                "type": "setfield",
+                "file": "file:///renoise/song.lua",
+                "extends": {{"start": 0, "finish": 10, "type": "table", "view": "table"}}
+            }}]
+        }}"#, name);
+        let class = |name: &str| format!(r#"{{
+            "name": "{}",
+            "type": "type",
+            "defines": [{{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///renoise/song.lua"}}]
+        }}"#, name);
+
+        let module = file(&format!("[{}, {}]", class("renoise.song.Track"), table("renoise.song")))?;
+        assert_eq!(module.title(), "renoise.song");
+
+        let ambiguous = file(&format!("[{}, {}]", class("renoise.Song"), class("renoise.Track")))?;
+        assert_eq!(ambiguous.title(), "song");
+
+        let variable = file(&format!("[{}]", table("x").replace(r#""type": "table""#, r#""type": "integer""#)))?;
+        assert_eq!(variable.title(), "song");
+
+        Ok(())
+    }
+
+    #[test]
+    fn directory_name() {
+        let file = |path: &str, depth| MetaFile {
+            path: PathBuf::from(path),
+            depth,
+            ..Default::default()
+        };
+
+        assert_eq!(file("renoise.lua", 0).directory_name(), None);
+        assert_eq!(file("renoise/song.lua", 1).directory_name(), Some("renoise".to_string()));
+        assert_eq!(file("renoise/song/track.lua", 2).directory_name(), Some("song".to_string()));
+        assert_eq!(file("song.lua", 1).directory_name(), None);
+        assert_eq!(file("/", 1).directory_name(), None);
+    }
+
+    #[test]
+    fn load_workspace_root_priority() -> anyhow::Result<()> {
+        let mut vendored = test_definition("file:///vendor/renoise.lua");
+        vendored.name = "renoise".into();
+        vendored.rawdesc = Some("vendored".into());
+
+        let mut local = test_definition("file:///local/renoise.lua");
+        local.name = "renoise".into();
+        local.rawdesc = Some("local".into());
+
+        let other = test_definition("file:///vendor/bit.lua");
+
+        let mut ws = Workspace::with_roots(["/vendor", "/local"]);
+
+        ws.load(vec![local, vendored, other])?;
+
+        let root_files: Vec<String> = ws.files.iter()
+            .map(|f| f.file_name())
+            .collect();
+
+        assert_eq!(root_files, vec!["bit.lua", "renoise.lua"]);
+
+        let renoise = &ws.files.get(1).unwrap().definitions;
+        assert_eq!(renoise.len(), 1);
+        assert_eq!(renoise[0].rawdesc.as_deref(), Some("local"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_hidden() -> anyhow::Result<()> {
+        let mut hidden = test_definition("file:///my/definitions/path/renoise.lua");
+        hidden.name = "internal".into();
+        hidden.rawdesc = Some("Not for you.\n@hidden".into());
+
+        let docs = vec![
+            test_definition("file:///my/definitions/path/renoise.lua"),
+            hidden,
+            test_definition("file:///my/definitions/path/renoise/midi.lua"),
+        ];
+        let mut private = test_definition("file:///my/definitions/path/bit.lua");
+        private.rawdesc = Some("@private".into());
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs.into_iter().chain([private]).collect())?;
+        ws.remove_hidden();
+
+        let root_files: Vec<String> = ws.files.iter().map(|f| f.file_name()).collect();
+        assert_eq!(root_files, vec!["renoise.lua"]);
+
+        let names: Vec<&str> = ws.files[0].definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["test"]);
+        assert_eq!(ws.files[0].sub_files.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_protected() -> anyhow::Result<()> {
+        let docs: Vec<Definition> = serde_json::from_str(r#"[{
+            "name": "Song",
+            "type": "type",
+            "defines": [{"start": 0, "finish": 10, "type": "doc.class", "file": "file:///my/definitions/path/song.lua"}],
+            "fields": [
+                {"name": "name", "type": "doc.field", "start": 1, "finish": 2, "file": "file:///my/definitions/path/song.lua"},
+                {"name": "_id", "type": "doc.field", "visible": "private", "start": 3, "finish": 4, "file": "file:///my/definitions/path/song.lua"},
+                {"name": "_cache", "type": "doc.field", "visible": "protected", "start": 5, "finish": 6, "file": "file:///my/definitions/path/song.lua"},
+                {"name": "_tracks", "type": "doc.field", "rawdesc": "@protected", "start": 7, "finish": 8, "file": "file:///my/definitions/path/song.lua"}
+            ]
+        }]"#)?;
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs)?;
+        let field_names = |ws: &Workspace| ws.files[0].definitions[0].fields.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+
+        ws.remove_hidden();
+        assert_eq!(field_names(&ws), vec!["name", "_cache", "_tracks"]);
+
+        ws.remove_protected();
+        assert_eq!(field_names(&ws), vec!["name"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn retain_files() -> anyhow::Result<()> {
+        let docs = vec![
+            test_definition("file:///my/definitions/path/renoise.lua"),
+            test_definition("file:///my/definitions/path/renoise/midi.lua"),
+            test_definition("file:///my/definitions/path/internal.lua"),
+            test_definition("file:///my/definitions/path/internal/util.lua"),
+            test_definition("file:///my/definitions/path/internal/util/api.lua"),
+        ];
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs)?;
+        ws.retain_files(|path| path != Path::new("internal.lua") && path != Path::new("internal/util.lua"));
+
+        let root_files: Vec<String> = ws.files.iter().map(|f| f.path.display().to_string()).collect();
+        assert_eq!(root_files, vec!["renoise.lua", "internal/util/api.lua"]);
+        assert_eq!(ws.files[0].sub_files.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_markdown() -> anyhow::Result<()> {
+        let file_urls = [
+            "file:///my/definitions/path/renoise.lua",
+            "file:///my/definitions/path/renoise/midi.lua",
+        ];
+
+        let docs: Vec<Definition> = file_urls
+            .iter()
+            .map(|&file| test_definition(file))
+            .collect();
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs)?;
+
+        let tmp_dir = TempDir::new("luacats-markdown")?;
+        ws.write_markdown(tmp_dir.path(), &MarkdownOptions::default())?;
+
+        let renoise = fs::read_to_string(tmp_dir.path().join("renoise.md"))?;
+        assert!(renoise.starts_with("## test { #test }"), "{}", renoise);
+        assert!(tmp_dir.path().join("renoise/midi.md").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn global_source_order() -> anyhow::Result<()> {
+        let definition = |name: &str, file: &str, start: u64| Definition {
+            name: name.into(),
+            defines: vec![Define { start, ..test_definition(file).defines.remove(0) }],
+            ..test_definition(file)
+        };
+        let docs = vec![
+            definition("renoise.midi.late", "file:///my/definitions/path/renoise/midi.lua", 20000),
+            definition("renoise.midi.early", "file:///my/definitions/path/renoise/midi.lua", 10000),
+            definition("bit", "file:///my/definitions/path/bit.lua", 0),
+            definition("renoise", "file:///my/definitions/path/renoise.lua", 0),
+        ];
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs)?;
+
+        let names: Vec<&str> = ws.definitions_in_source_order().into_iter().map(|definition| definition.name.as_str()).collect();
+        assert_eq!(names, vec!["bit", "renoise", "renoise.midi.early", "renoise.midi.late"]);
+
+        let markdown = ws.render_markdown(&MarkdownOptions::default())?;
+        let headings: Vec<&str> = markdown.lines().filter(|line| line.starts_with("## ")).collect();
+        assert_eq!(headings, vec![
+            "## bit { #bit }",
+            "## renoise { #renoise }",
+            "## renoise.midi.early { #renoise-midi-early }",
+            "## renoise.midi.late { #renoise-midi-late }",
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn render_single_file() -> anyhow::Result<()> {
+        let mut renoise = test_definition("file:///my/definitions/path/renoise.lua");
+        renoise.name = "renoise".into();
+        let mut midi = test_definition("file:///my/definitions/path/renoise/midi.lua");
+        midi.name = "renoise.Midi".into();
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(vec![renoise, midi])?;
+
+        for path in ["renoise/midi.lua", "/my/definitions/path/renoise/midi.lua"] {
+            let markdown = ws.render_file_markdown(path, &MarkdownOptions::default())?;
+            assert!(markdown.contains("## renoise.Midi { #renoise-midi }"), "{}", markdown);
+            assert!(!markdown.contains("## renoise {"), "{}", markdown);
+        }
+
+        let err = ws.render_file_markdown("missing.lua", &MarkdownOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "No definitions were found in missing.lua");
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedupe_definitions() -> anyhow::Result<()> {
+        let mut song = test_definition("file:///my/definitions/path/util.lua");
+        song.name = "Song".into();
+        song.defines.push(Define {
+            lua_type: DefinitionType::DocClass,
+            file: "file:///my/definitions/path/song.lua".into(),
+            ..song.defines[0].clone()
+        });
+        song.defines.push(song.defines[0].clone());
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(vec![song])?;
+
+        let files: Vec<(String, usize)> = ws.files.iter().map(|f| (f.file_name(), f.definitions.len())).collect();
+        assert_eq!(files, vec![("song.lua".into(), 1)]);
+        assert_eq!(ws.files[0].also_defined_in, BTreeMap::from([("Song".into(), vec![PathBuf::from("util.lua")])]));
+
+        let markdown = ws.render_file_markdown("song.lua", &MarkdownOptions::default())?;
+        assert!(markdown.contains("## Song { #song }\n\n*Also defined in `util.lua`*\n"), "{}", markdown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_json() -> anyhow::Result<()> {
+        let file_urls = [
+            "file:///my/definitions/path/renoise.lua",
+            "file:///my/definitions/path/renoise/midi.lua",
+        ];
+
+        let docs: Vec<Definition> = file_urls
+            .iter()
+            .map(|&file| test_definition(file))
+            .collect();
+
+        let mut ws = Workspace::new("/my/definitions/path");
+        ws.load(docs)?;
+
+        let tmp_dir = TempDir::new("luacats-export")?;
+        let path = tmp_dir.path().join("workspace.json");
+        ws.export_json(&path)?;
+
+        let exported: Workspace = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(exported, ws);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_external_workspace() -> anyhow::Result<()> {
+        let mut string = test_definition("file:///usr/share/luals/meta/string.lua");
+        string.name = "string".into();
+
+        let docs = vec![
+            test_definition("file:///my/definitions/path/bit.lua"),
+            string,
+        ];
+
+        let ws = Workspace::external(&docs, &[PathBuf::from("/my/definitions/path")], "stdlib")?;
+
+        assert_eq!(ws.root, PathBuf::from("/usr/share/luals/meta"));
+        assert_eq!(ws.roots, vec![PathBuf::from("/usr/share/luals/meta")]);
+        assert_eq!(ws.files.len(), 1);
+        assert_eq!(ws.files[0].path, PathBuf::from("stdlib/meta/string.lua"));
+        assert_eq!(ws.files[0].definitions[0].name, "string");
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_external_same_file_names() -> anyhow::Result<()> {
+        let mut string = test_definition("file:///usr/share/luals/meta/string.lua");
+        string.name = "string".into();
+        let mut utf8 = test_definition("file:///usr/share/luals/meta/sub/utf8.lua");
+        utf8.name = "utf8".into();
+        let mut addon_string = test_definition("file:///home/me/addons/meta/string.lua");
+        addon_string.name = "string.buffer".into();
+        let mut shadowed = test_definition("file:///usr/share/luals/meta/string.lua");
+        shadowed.name = "shadowed".into();
+        let mut shadowing = test_definition("file:///home/me/addons/meta/string.lua");
+        shadowing.name = "shadowed".into();
+
+        let docs = vec![string, utf8, addon_string, shadowed, shadowing];
+        let ws = Workspace::external(&docs, &[PathBuf::from("/my/definitions/path")], "stdlib")?;
+
+        assert_eq!(ws.roots, vec![PathBuf::from("/home/me/addons/meta"), PathBuf::from("/usr/share/luals/meta")]);
+        let paths: Vec<&Path> = ws.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(paths, vec![
+            Path::new("stdlib/meta/string.lua"),
+            Path::new("stdlib/meta-2/string.lua"),
+            Path::new("stdlib/meta-2/sub/utf8.lua"),
+        ]);
+        // The symbol defined in both folders is only kept from the last
+        let names: Vec<&str> = ws.files[0].definitions.iter().map(|definition| definition.name.as_str()).collect();
+        assert_eq!(names, vec!["string.buffer"]);
+        let names: Vec<&str> = ws.files[1].definitions.iter().map(|definition| definition.name.as_str()).collect();
+        assert_eq!(names, vec!["string", "shadowed"]);
+
+        Ok(())
+    }
 }
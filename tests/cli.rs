@@ -0,0 +1,35 @@
+use mdbook::{book::Book, renderer::RenderContext, Config};
+use std::process::{Command, Stdio};
+
+#[test]
+fn markdown_to_stdout() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mdbook-luacats"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["markdown", "--definitions-path", "test_book/library", "--file", "bit.lua"])
+        .output()
+        .expect("mdbook-luacats runs");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Only the rendered markdown is printed, not the doc.json LuaLS generated
+    assert!(stdout.starts_with("## bit { #bit }\n"), "{}", stdout);
+    assert!(!stdout.contains("\"defines\""), "{}", stdout);
+}
+
+#[test]
+fn assets_renderer() {
+    let tmp_dir = tempdir::TempDir::new("luacats-assets-renderer").expect("temp dir");
+    let ctx = RenderContext::new(tmp_dir.path(), Book::new(), Config::default(), tmp_dir.path().join("book/luacats-assets"));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mdbook-luacats"))
+        .arg("assets")
+        .stdin(Stdio::piped())
+        .spawn()
+        .expect("mdbook-luacats runs");
+    serde_json::to_writer(child.stdin.take().unwrap(), &ctx).expect("context is written");
+    let status = child.wait().expect("mdbook-luacats exits");
+
+    assert!(status.success());
+    assert!(tmp_dir.path().join("book/html/luacats/luacats.css").is_file());
+    assert!(tmp_dir.path().join("book/html/luacats/luacats.js").is_file());
+}